@@ -0,0 +1,4 @@
+DROP INDEX index_posts_deleted_at;
+
+ALTER TABLE post_history DROP COLUMN deleted_at;
+ALTER TABLE posts DROP COLUMN deleted_at;
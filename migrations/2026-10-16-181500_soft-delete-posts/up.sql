@@ -0,0 +1,4 @@
+ALTER TABLE posts ADD COLUMN deleted_at TEXT;
+ALTER TABLE post_history ADD COLUMN deleted_at TEXT;
+
+CREATE INDEX index_posts_deleted_at ON posts(deleted_at);
@@ -0,0 +1,13 @@
+-- Syndication targets chosen for a post via mp-syndicate-to. url is filled in once the post has
+-- been syndicated.
+CREATE TABLE syndications(
+    id INTEGER PRIMARY KEY NOT NULL,
+    post_id INTEGER REFERENCES posts(id) NOT NULL,
+    target TEXT NOT NULL,
+    url TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX index_syndications_post_id ON syndications(post_id);
+CREATE UNIQUE INDEX index_syndications_post_target ON syndications(post_id, target);
@@ -0,0 +1,2 @@
+ALTER TABLE post_history DROP COLUMN summary;
+ALTER TABLE posts DROP COLUMN summary;
@@ -0,0 +1,2 @@
+ALTER TABLE posts ADD COLUMN summary TEXT;
+ALTER TABLE post_history ADD COLUMN summary TEXT;
@@ -0,0 +1,6 @@
+ALTER TABLE post_history DROP COLUMN repost_of;
+ALTER TABLE post_history DROP COLUMN like_of;
+ALTER TABLE post_history DROP COLUMN in_reply_to;
+ALTER TABLE posts DROP COLUMN repost_of;
+ALTER TABLE posts DROP COLUMN like_of;
+ALTER TABLE posts DROP COLUMN in_reply_to;
@@ -0,0 +1,6 @@
+ALTER TABLE posts ADD COLUMN in_reply_to TEXT;
+ALTER TABLE posts ADD COLUMN like_of TEXT;
+ALTER TABLE posts ADD COLUMN repost_of TEXT;
+ALTER TABLE post_history ADD COLUMN in_reply_to TEXT;
+ALTER TABLE post_history ADD COLUMN like_of TEXT;
+ALTER TABLE post_history ADD COLUMN repost_of TEXT;
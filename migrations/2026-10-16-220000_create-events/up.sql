@@ -0,0 +1,15 @@
+-- start/end/location/url of h-event posts. starts_at and ends_at are stored in UTC using the same
+-- format as posts.created_at so they can be compared as text.
+CREATE TABLE events(
+    id INTEGER PRIMARY KEY NOT NULL,
+    post_id INTEGER REFERENCES posts(id) NOT NULL,
+    starts_at TEXT NOT NULL,
+    ends_at TEXT,
+    location TEXT,
+    url TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX index_events_post_id ON events(post_id);
+CREATE INDEX index_events_starts_at ON events(starts_at);
@@ -0,0 +1,14 @@
+-- The reviewed item and rating of h-review posts. The review body is the post content.
+CREATE TABLE reviews(
+    id INTEGER PRIMARY KEY NOT NULL,
+    post_id INTEGER REFERENCES posts(id) NOT NULL,
+    item_name TEXT,
+    item_url TEXT,
+    rating REAL,
+    best REAL,
+    worst REAL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX index_reviews_post_id ON reviews(post_id);
@@ -0,0 +1,11 @@
+-- Nested microformats objects (e.g. the h-food of an ate or drank property) attached to a post,
+-- stored as the mf2 json the client sent.
+CREATE TABLE post_objects(
+    id INTEGER PRIMARY KEY NOT NULL,
+    post_id INTEGER REFERENCES posts(id) NOT NULL,
+    property TEXT NOT NULL,
+    object TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX index_post_objects_post_id ON post_objects(post_id);
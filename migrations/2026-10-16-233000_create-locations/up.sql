@@ -0,0 +1,13 @@
+-- The checkin venue and/or location of a post. property is either 'checkin' or 'location'.
+CREATE TABLE locations(
+    id INTEGER PRIMARY KEY NOT NULL,
+    post_id INTEGER REFERENCES posts(id) NOT NULL,
+    property TEXT NOT NULL,
+    name TEXT,
+    url TEXT,
+    latitude REAL,
+    longitude REAL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX index_locations_post_property ON locations(post_id, property);
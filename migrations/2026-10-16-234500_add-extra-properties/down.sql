@@ -0,0 +1,2 @@
+ALTER TABLE post_history DROP COLUMN extra_properties;
+ALTER TABLE posts DROP COLUMN extra_properties;
@@ -0,0 +1,3 @@
+-- mf2 properties we have no dedicated storage for, as a json object of property name to values.
+ALTER TABLE posts ADD COLUMN extra_properties TEXT;
+ALTER TABLE post_history ADD COLUMN extra_properties TEXT;
@@ -0,0 +1,4 @@
+DROP INDEX index_posts_post_status;
+
+ALTER TABLE post_history DROP COLUMN post_status;
+ALTER TABLE posts DROP COLUMN post_status;
@@ -0,0 +1,5 @@
+-- 'published' or 'draft'. Published posts with a created_at in the future are scheduled.
+ALTER TABLE posts ADD COLUMN post_status TEXT NOT NULL DEFAULT 'published';
+ALTER TABLE post_history ADD COLUMN post_status TEXT NOT NULL DEFAULT 'published';
+
+CREATE INDEX index_posts_post_status ON posts(post_status);
@@ -0,0 +1,4 @@
+DROP INDEX index_posts_visibility;
+
+ALTER TABLE post_history DROP COLUMN visibility;
+ALTER TABLE posts DROP COLUMN visibility;
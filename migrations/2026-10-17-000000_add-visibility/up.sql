@@ -0,0 +1,5 @@
+-- 'public', 'unlisted' (not in listings or feeds) or 'private' (only shown to authorized viewers)
+ALTER TABLE posts ADD COLUMN visibility TEXT NOT NULL DEFAULT 'public';
+ALTER TABLE post_history ADD COLUMN visibility TEXT NOT NULL DEFAULT 'public';
+
+CREATE INDEX index_posts_visibility ON posts(visibility);
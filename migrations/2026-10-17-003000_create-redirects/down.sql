@@ -0,0 +1,2 @@
+DROP TRIGGER posts_slug_redirect;
+DROP TABLE redirects;
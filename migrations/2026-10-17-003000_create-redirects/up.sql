@@ -0,0 +1,18 @@
+-- Old slugs of posts that have been renamed. They redirect to the post's current slug.
+CREATE TABLE redirects(
+    id INTEGER PRIMARY KEY NOT NULL,
+    from_slug TEXT NOT NULL,
+    post_id INTEGER REFERENCES posts(id) NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX index_redirects_from_slug ON redirects(from_slug);
+
+-- Recorded by a trigger so that slugs changed outside of Micropub updates (e.g. by hand) are
+-- redirected too. A slug that is taken again stops redirecting.
+CREATE TRIGGER posts_slug_redirect AFTER UPDATE OF slug ON posts
+WHEN OLD.slug != NEW.slug
+BEGIN
+    INSERT OR REPLACE INTO redirects (from_slug, post_id) VALUES (OLD.slug, NEW.id);
+    DELETE FROM redirects WHERE from_slug = NEW.slug;
+END;
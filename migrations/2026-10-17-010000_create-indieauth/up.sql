@@ -0,0 +1,26 @@
+-- Authorization codes and access tokens issued by the built in IndieAuth server. Only sha256
+-- hashes of codes and tokens are stored.
+CREATE TABLE auth_codes(
+    id INTEGER PRIMARY KEY NOT NULL,
+    code_hash TEXT NOT NULL,
+    client_id TEXT NOT NULL,
+    redirect_uri TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    code_challenge TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    used_at TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX index_auth_codes_code_hash ON auth_codes(code_hash);
+
+CREATE TABLE access_tokens(
+    id INTEGER PRIMARY KEY NOT NULL,
+    token_hash TEXT NOT NULL,
+    client_id TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    revoked_at TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX index_access_tokens_token_hash ON access_tokens(token_hash);
@@ -0,0 +1,22 @@
+-- Webmentions received for posts. They are stored as pending when received and verified in the
+-- background by fetching the source, only verified mentions are shown with the post. Sending the
+-- same source and target again re-verifies the mention.
+CREATE TABLE mentions(
+    id INTEGER PRIMARY KEY NOT NULL,
+    post_id INTEGER REFERENCES posts(id) NOT NULL,
+    source TEXT NOT NULL,
+    target TEXT NOT NULL,
+    -- pending, verified, rejected (the source doesn't link to the target) or deleted (410 Gone)
+    status TEXT NOT NULL DEFAULT 'pending',
+    -- reply, like, repost or mention
+    mention_type TEXT NOT NULL DEFAULT 'mention',
+    author_name TEXT,
+    author_url TEXT,
+    author_photo TEXT,
+    content TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX index_mentions_source_target ON mentions(source, target);
+CREATE INDEX index_mentions_post_id ON mentions(post_id);
@@ -1,12 +1,19 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct TokenValidateResponse {
     pub me: String,
     pub client_id: String,
     pub issued_at: i64,
     pub scope: String,
     pub nonce: i64,
+    /// Unix time the token expires at, if the token endpoint says
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
 }
 
 impl TokenValidateResponse {
@@ -17,4 +24,265 @@ impl TokenValidateResponse {
             self.scope.split_whitespace().collect()
         }
     }
-}
\ No newline at end of file
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes().contains(&scope)
+    }
+}
+
+/// Response from a token introspection endpoint, see
+/// https://indieauth.spec.indieweb.org/#access-token-verification-response
+#[derive(Debug, Deserialize, Serialize)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub me: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+}
+
+impl IntrospectionResponse {
+    /// The equivalent legacy token endpoint response, or None if the token is not active or
+    /// expired before `now` (a unix time).
+    pub fn into_validate_response(self, now: i64) -> Option<TokenValidateResponse> {
+        if !self.active || self.exp.is_some_and(|exp| exp <= now) {
+            return None;
+        }
+
+        Some(TokenValidateResponse {
+            me: self.me?,
+            client_id: self.client_id.unwrap_or_default(),
+            issued_at: self.iat.unwrap_or_default(),
+            scope: self.scope.unwrap_or_default(),
+            nonce: 0,
+            exp: self.exp,
+        })
+    }
+}
+
+/// Finds the indieauth-metadata URL in a `Link` header value, e.g.
+/// `<https://example.com/.well-known/oauth-authorization-server>; rel="indieauth-metadata"`.
+pub fn metadata_link_from_header(link: &str) -> Option<String> {
+    link.split(',').find_map(|value| {
+        let mut parts = value.split(';');
+        let url = parts.next()?.trim().strip_prefix('<')?.strip_suffix('>')?;
+        parts
+            .filter_map(|param| param.trim().strip_prefix("rel="))
+            .any(|rels| rels.trim_matches('"').split_whitespace().any(|rel| rel == "indieauth-metadata"))
+            .then(|| url.to_string())
+    })
+}
+
+/// Finds the indieauth-metadata URL in the `<link>` elements of an HTML page.
+pub fn metadata_link_from_html(html: &str) -> Option<String> {
+    let selector = scraper::Selector::parse(r#"link[rel~="indieauth-metadata"][href]"#)
+        .expect("valid selector");
+    scraper::Html::parse_document(html)
+        .select(&selector)
+        .next()
+        .and_then(|link| link.value().attr("href"))
+        .map(String::from)
+}
+
+/// How long a rejected token is remembered for, if less than the max TTL.
+const REJECTED_TOKEN_TTL: Duration = Duration::from_secs(60);
+
+/// Entries are pruned once the cache grows to this many tokens.
+const TOKEN_CACHE_PRUNE_SIZE: usize = 1024;
+
+/// How long an introspection endpoint discovered from host_website is used before it is
+/// discovered again.
+const DISCOVERED_ENDPOINT_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// In-process cache of token verification results, keyed by a hash of the token, so that every
+/// request made with the same token doesn't need a round trip to the token endpoint. It also
+/// holds the introspection endpoint discovered from host_website.
+#[derive(Debug, Default)]
+pub struct TokenCache {
+    entries: Mutex<HashMap<String, (Instant, Option<TokenValidateResponse>)>>,
+    introspection_endpoint: Mutex<Option<(Instant, String)>>,
+}
+
+impl TokenCache {
+    /// The cached result for a token hash, `Some(None)` if the token was rejected.
+    pub fn get(&self, token_hash: &str, now: Instant) -> Option<Option<TokenValidateResponse>> {
+        let entries = self.entries.lock().expect("token cache lock poisoned");
+        entries
+            .get(token_hash)
+            .filter(|(expires_at, _)| *expires_at > now)
+            .map(|(_, response)| response.clone())
+    }
+
+    /// Caches a valid token until the earlier of its `exp` (compared to `unix_now`) and max_ttl.
+    pub fn insert_valid(
+        &self,
+        token_hash: &str,
+        response: &TokenValidateResponse,
+        max_ttl: Duration,
+        now: Instant,
+        unix_now: i64,
+    ) {
+        let ttl = match response.exp {
+            Some(exp) => max_ttl.min(Duration::from_secs(exp.saturating_sub(unix_now).max(0) as u64)),
+            None => max_ttl,
+        };
+        self.insert(token_hash, Some(response.clone()), ttl, now);
+    }
+
+    /// Caches a token the token endpoint rejected.
+    pub fn insert_rejected(&self, token_hash: &str, max_ttl: Duration, now: Instant) {
+        self.insert(token_hash, None, max_ttl.min(REJECTED_TOKEN_TTL), now);
+    }
+
+    fn insert(&self, token_hash: &str, response: Option<TokenValidateResponse>, ttl: Duration, now: Instant) {
+        if ttl.is_zero() {
+            return;
+        }
+
+        let mut entries = self.entries.lock().expect("token cache lock poisoned");
+        if entries.len() >= TOKEN_CACHE_PRUNE_SIZE {
+            entries.retain(|_, (expires_at, _)| *expires_at > now);
+        }
+        entries.insert(token_hash.to_string(), (now + ttl, response));
+    }
+
+    /// The discovered introspection endpoint, if it hasn't expired.
+    pub fn introspection_endpoint(&self, now: Instant) -> Option<String> {
+        let endpoint = self.introspection_endpoint.lock().expect("token cache lock poisoned");
+        endpoint
+            .as_ref()
+            .filter(|(expires_at, _)| *expires_at > now)
+            .map(|(_, endpoint)| endpoint.clone())
+    }
+
+    pub fn insert_introspection_endpoint(&self, endpoint: &str, now: Instant) {
+        let mut cached = self.introspection_endpoint.lock().expect("token cache lock poisoned");
+        *cached = Some((now + DISCOVERED_ENDPOINT_TTL, endpoint.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{metadata_link_from_header, metadata_link_from_html, IntrospectionResponse, TokenCache, TokenValidateResponse};
+
+    fn response_with_scope(scope: &str) -> TokenValidateResponse {
+        TokenValidateResponse {
+            me: "https://example.com".into(),
+            client_id: "https://test-client.example.com".into(),
+            issued_at: 1640995200,
+            scope: scope.into(),
+            nonce: 12345,
+            exp: None,
+        }
+    }
+
+    #[test]
+    fn has_scope_matches_whole_scopes() {
+        let response = response_with_scope("create update");
+        assert!(response.has_scope("create"));
+        assert!(response.has_scope("update"));
+        assert!(!response.has_scope("delete"));
+        assert!(!response.has_scope("up"));
+    }
+
+    #[test]
+    fn has_scope_empty_scope() {
+        assert!(!response_with_scope("").has_scope("create"));
+    }
+
+    #[test]
+    fn introspection_response_active() {
+        let response: IntrospectionResponse = serde_json::from_value(json!({
+            "active": true,
+            "me": "https://example.com/",
+            "client_id": "https://app.example.com/",
+            "scope": "create update",
+            "exp": 2000,
+            "iat": 1000,
+        })).unwrap();
+        let validate_response = response.into_validate_response(1500).unwrap();
+        assert_eq!(validate_response.me, "https://example.com/");
+        assert!(validate_response.has_scope("update"));
+        assert_eq!(validate_response.issued_at, 1000);
+        assert_eq!(validate_response.exp, Some(2000));
+    }
+
+    #[test]
+    fn introspection_response_inactive_or_expired() {
+        let inactive: IntrospectionResponse = serde_json::from_value(json!({"active": false})).unwrap();
+        assert!(inactive.into_validate_response(1500).is_none());
+
+        let expired: IntrospectionResponse = serde_json::from_value(json!({
+            "active": true,
+            "me": "https://example.com/",
+            "exp": 1000,
+        })).unwrap();
+        assert!(expired.into_validate_response(1500).is_none());
+    }
+
+    #[test]
+    fn metadata_link_discovery() {
+        assert_eq!(
+            metadata_link_from_header(r#"<https://example.com/webmention>; rel="webmention", </.well-known/oauth-authorization-server>; rel="indieauth-metadata""#),
+            Some("/.well-known/oauth-authorization-server".into())
+        );
+        assert_eq!(metadata_link_from_header(r#"<https://example.com/webmention>; rel="webmention""#), None);
+        assert_eq!(
+            metadata_link_from_html(r#"<html><head><link rel="me authn" href="https://github.com/x"><link rel="indieauth-metadata" href="https://auth.example.com/metadata"></head></html>"#),
+            Some("https://auth.example.com/metadata".into())
+        );
+        assert_eq!(metadata_link_from_html("<html><head></head></html>"), None);
+    }
+
+    #[test]
+    fn token_cache_ttl() {
+        let cache = TokenCache::default();
+        let now = Instant::now();
+        let max_ttl = Duration::from_secs(300);
+
+        cache.insert_valid("a", &response_with_scope("create"), max_ttl, now, 1000);
+        assert!(cache.get("a", now + Duration::from_secs(299)).unwrap().is_some());
+        assert!(cache.get("a", now + Duration::from_secs(300)).is_none());
+
+        // exp shortens the TTL, an already expired token isn't cached
+        let mut expiring = response_with_scope("create");
+        expiring.exp = Some(1010);
+        cache.insert_valid("b", &expiring, max_ttl, now, 1000);
+        assert!(cache.get("b", now + Duration::from_secs(9)).is_some());
+        assert!(cache.get("b", now + Duration::from_secs(10)).is_none());
+        cache.insert_valid("c", &expiring, max_ttl, now, 2000);
+        assert!(cache.get("c", now).is_none());
+
+        cache.insert_rejected("d", max_ttl, now);
+        assert!(cache.get("d", now).unwrap().is_none());
+        assert!(cache.get("d", now + Duration::from_secs(60)).is_none());
+
+        // a max TTL of zero disables caching
+        cache.insert_valid("e", &response_with_scope("create"), Duration::ZERO, now, 1000);
+        cache.insert_rejected("f", Duration::ZERO, now);
+        assert!(cache.get("e", now).is_none());
+        assert!(cache.get("f", now).is_none());
+    }
+
+    #[test]
+    fn discovered_introspection_endpoint_expires() {
+        let cache = TokenCache::default();
+        let now = Instant::now();
+        assert_eq!(cache.introspection_endpoint(now), None);
+
+        cache.insert_introspection_endpoint("https://auth.example.com/introspect", now);
+        assert_eq!(
+            cache.introspection_endpoint(now + Duration::from_secs(3599)).as_deref(),
+            Some("https://auth.example.com/introspect")
+        );
+        assert_eq!(cache.introspection_endpoint(now + Duration::from_secs(3600)), None);
+    }
+}
@@ -1,13 +1,5 @@
-use std::sync::Arc;
-use axum::{
-    extract::State,
-    http::StatusCode,
-    response::Json,
-    routing::get,
-    Router,
-};
 use clap::Parser;
-use micropub_rs::auth::TokenValidateResponse;
+use micropub_rs::fake_auth::{self, FakeAuthConfig};
 
 #[derive(Parser, Clone)]
 #[command(name = "fake_auth_server")]
@@ -22,48 +14,55 @@ struct Args {
     #[arg(long, default_value = "https://example.com")]
     me: String,
 
-    /// The 'client_id' field in auth response  
+    /// The 'client_id' field in auth response
     #[arg(long, default_value = "https://test-client.example.com")]
     client_id: String,
 
     /// The 'scope' field in auth response
     #[arg(long, default_value = "create update delete")]
     scope: String,
-}
 
-async fn validate_token(State(config): State<Arc<Args>>) -> Result<Json<TokenValidateResponse>, StatusCode> {
-    // Return a fake but valid token validation response
-    let response = TokenValidateResponse {
-        me: config.me.clone(),
-        client_id: config.client_id.clone(),
-        issued_at: 1640995200, // 2022-01-01 00:00:00 UTC
-        scope: config.scope.clone(),
-        nonce: 12345,
-    };
-    
-    Ok(Json(response))
+    /// Token that POST /introspect reports as not active
+    #[arg(long, default_value = "inactive")]
+    inactive_token: String,
+
+    /// Seconds until tokens expire, reported as 'exp' by POST /introspect
+    #[arg(long)]
+    expires_in: Option<i64>,
+
+    /// Number of token verification requests to answer with 500 before verifying tokens
+    #[arg(long, default_value = "0")]
+    fail_requests: usize,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let config = Arc::new(Args::parse());
-    
-    let app = Router::new()
-        .route("/token", get(validate_token))
-        .with_state(config.clone());
+    let args = Args::parse();
+    let config = FakeAuthConfig {
+        me: args.me,
+        client_id: args.client_id,
+        scope: args.scope,
+        inactive_token: args.inactive_token,
+        expires_in: args.expires_in,
+        fail_requests: args.fail_requests,
+    };
 
-    let bind_addr = format!("127.0.0.1:{}", config.port);
+    let bind_addr = format!("127.0.0.1:{}", args.port);
     let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
-    
+
     println!("Fake auth server listening on {}", listener.local_addr()?);
     println!("Configuration:");
     println!("  me: {}", config.me);
     println!("  client_id: {}", config.client_id);
     println!("  scope: {}", config.scope);
+    println!("  inactive_token: {}", config.inactive_token);
+    println!("  expires_in: {:?}", config.expires_in);
+    println!("  fail_requests: {}", config.fail_requests);
     println!();
     println!("Responding to GET /token with valid TokenValidateResponse");
-    
-    axum::serve(listener, app).await?;
-    
+    println!("Responding to POST /introspect with an IntrospectionResponse, active unless the token is inactive_token");
+
+    axum::serve(listener, fake_auth::router(config)).await?;
+
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::env;
 use std::sync::Arc;
 
@@ -6,15 +7,16 @@ use log::{debug, error, info};
 use serde_json::json;
 
 use axum::{
-    extract::{Path, DefaultBodyLimit},
+    extract::{Path, DefaultBodyLimit, Query},
     http::{HeaderMap, StatusCode},
-    routing::{on, on_service, post, MethodFilter},
+    routing::{get, on, on_service, post, MethodFilter},
     Router,
 };
 use tower_http::services::ServeDir;
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::fmt::format::FmtSpan;
 
+use micropub_rs::auth;
 use micropub_rs::handler;
 use micropub_rs::handlers;
 use micropub_rs::templates;
@@ -47,6 +49,8 @@ async fn main() -> Result<(), anyhow::Error> {
     let dbpool = Arc::new(micropub_rs::new_dbconn_pool(&site_config.database_url)?);
     let micropub_db = Arc::new(handler::MicropubDB::new(dbpool.clone()));
     let http_client = reqwest::Client::new();
+    let token_cache = Arc::new(auth::TokenCache::default());
+    let verifications = handlers::Verifications::default();
     info!("created dbpool from {:?}", &site_config.database_url);
 
     let template_pattern = std::path::Path::new(&site_config.template_dir).join("templates/**/*.html");
@@ -70,10 +74,22 @@ async fn main() -> Result<(), anyhow::Error> {
 
     let media_config = Arc::new(json!({
         "media-endpoint": site_config.micropub.media_endpoint,
+        "syndicate-to": site_config.micropub.syndicate_to,
+        "q": handlers::micropub::SUPPORTED_QUERIES,
     }));
 
-    let atom_ctx = base_ctx.clone();
+    let mut atom_ctx = base_ctx.clone();
+    atom_ctx.insert("FEED_TITLE", site_config.site.feed_title());
+    atom_ctx.insert("SITE_URL", &site_config.micropub.site_url(""));
+    atom_ctx.insert(
+        "SITE_DOMAIN",
+        &site_config.micropub.site_domain().ok_or(anyhow!("host_website is not a valid url"))?,
+    );
+    atom_ctx.insert("AUTHOR_NAME", site_config.site.author_name());
+    atom_ctx.insert("AUTHOR_URL", &site_config.site.author_url());
+    atom_ctx.insert("AUTHOR_EMAIL", &site_config.site.author_email());
 
+    let consent_templates = Arc::new(templates::Templates::consent_default(base_ctx.clone()));
     let templates = Arc::new(templates::Templates::new(tera, base_ctx));
 
     let app = Router::new()
@@ -101,6 +117,18 @@ async fn main() -> Result<(), anyhow::Error> {
                 }
             ),
         )
+        .route(
+            "/events",
+            on(
+                MethodFilter::GET.or(MethodFilter::HEAD),
+                {
+                    let dbpool = dbpool.clone();
+                    let templates = templates.clone();
+                    let c = site_config.clone();
+                    move || handlers::get_events_handler(dbpool.clone(), templates.clone(), c.clone())
+                }
+            ),
+        )
         .route(
             "/feeds/all.atom.xml",
             on(
@@ -119,11 +147,13 @@ async fn main() -> Result<(), anyhow::Error> {
                 let db = micropub_db.clone();
                 let client = http_client.clone();
                 let cfg = site_config.clone();
+                let token_cache = token_cache.clone();
 
                 move |headers, multipart| {
                     handlers::handle_media_upload(
                         client.clone(), 
                         db.clone(),
+                        token_cache.clone(),
                         headers,
                         multipart,
                         cfg.clone(),
@@ -155,15 +185,17 @@ async fn main() -> Result<(), anyhow::Error> {
                 let db = micropub_db.clone();
                 let client = http_client.clone();
                 let c = site_config.clone();
+                let token_cache = token_cache.clone();
 
-                move |headers: HeaderMap, body| {
-                    handlers::handle_post(client.clone(), db.clone(), c.clone(), headers, body)
+                move |request| {
+                    handlers::handle_post(client.clone(), db.clone(), token_cache.clone(), c.clone(), request)
                 }
             }).get({
                 let client = http_client.clone();
                 let config = media_config.clone();
                 let c = site_config.clone();
                 let db = micropub_db.clone();
+                let token_cache = token_cache.clone();
 
                 move |headers, query| {
                     handlers::handle_query(
@@ -172,7 +204,8 @@ async fn main() -> Result<(), anyhow::Error> {
                         c.clone(),
                         headers,
                         query,
-                        db.clone()
+                        db.clone(),
+                        token_cache.clone(),
                     )
                 }
 
@@ -192,6 +225,39 @@ async fn main() -> Result<(), anyhow::Error> {
                 }
             ),
         )
+        .route(
+            "/preview/*post_slug",
+            on(
+                MethodFilter::GET.or(MethodFilter::HEAD),
+                {
+                    let dbpool = dbpool.clone();
+                    let client = http_client.clone();
+                    let token_cache = token_cache.clone();
+                    let templates = templates.clone();
+                    let c = site_config.clone();
+                    move |Path(post_slug): Path<String>, Query(mut query): Query<HashMap<String, String>>| {
+                        handlers::get_preview_handler(
+                            post_slug,
+                            query.remove("token"),
+                            client.clone(),
+                            dbpool.clone(),
+                            token_cache.clone(),
+                            templates.clone(),
+                            c.clone(),
+                        )
+                    }
+                }
+            ),
+        )
+        .route(
+            "/webmention",
+            post({
+                let db = micropub_db.clone();
+                let verifications = verifications.clone();
+                let c = site_config.clone();
+                move |form| handlers::handle_webmention(form, verifications.clone(), db.clone(), c.clone())
+            }),
+        )
         .nest(
             "/theme",
             Router::new().route(
@@ -213,14 +279,72 @@ async fn main() -> Result<(), anyhow::Error> {
                 MethodFilter::GET.or(MethodFilter::HEAD),
                 {
                     let dbpool = dbpool.clone();
+                    let client = http_client.clone();
+                    let token_cache = token_cache.clone();
                     let c = site_config.clone();
-                    move |Path(post_slug): Path<String>| {
-                        handlers::get_post_handler(post_slug, dbpool.clone(), templates.clone(), c.clone())
+                    move |Path(post_slug): Path<String>, headers: HeaderMap, Query(mut query): Query<HashMap<String, String>>| {
+                        handlers::get_post_handler(
+                            post_slug,
+                            headers,
+                            query.remove("token"),
+                            client.clone(),
+                            dbpool.clone(),
+                            token_cache.clone(),
+                            templates.clone(),
+                            c.clone(),
+                        )
                     }
                 }
             )
         );
 
+    // Built in IndieAuth server, only routed when configured
+    let app = if site_config.micropub.indieauth.is_some() {
+        info!("serving built in indieauth authorization and token endpoints");
+        app.route(
+            "/auth",
+            get({
+                let templates = consent_templates.clone();
+                move |query| handlers::get_authorization_handler(query, templates.clone())
+            }).post({
+                let db = micropub_db.clone();
+                let templates = consent_templates.clone();
+                let c = site_config.clone();
+                move |form| {
+                    handlers::post_authorization_handler(form, db.clone(), templates.clone(), c.clone())
+                }
+            })
+        )
+        .route(
+            "/token",
+            get({
+                let db = micropub_db.clone();
+                let c = site_config.clone();
+                move |headers| handlers::get_token_handler(headers, db.clone(), c.clone())
+            }).post({
+                let db = micropub_db.clone();
+                let c = site_config.clone();
+                move |form| handlers::post_token_handler(form, db.clone(), c.clone())
+            })
+        )
+        .route(
+            "/revoke",
+            post({
+                let db = micropub_db.clone();
+                move |form| handlers::post_revoke_handler(form, db.clone())
+            })
+        )
+        .route(
+            "/.well-known/oauth-authorization-server",
+            get({
+                let c = site_config.clone();
+                move || handlers::get_metadata_handler(c.clone())
+            })
+        )
+    } else {
+        app
+    };
+
     axum::serve(
         tokio::net::TcpListener::bind("0.0.0.0:3030").await?,
         app
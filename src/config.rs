@@ -1,4 +1,4 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize)]
 pub struct MicropubSiteConfig {
@@ -17,6 +17,40 @@ pub struct SiteConfig {
     pub menu_items: Vec<(String, String)>,
     pub socials: Vec<String>,
 
+    /// Title of the atom feed, defaults to site_name
+    #[serde(default)]
+    pub feed_title: Option<String>,
+
+    #[serde(default)]
+    pub author: Option<AuthorConfig>,
+}
+
+impl SiteConfig {
+    pub fn feed_title(&self) -> &str {
+        self.feed_title.as_deref().unwrap_or(&self.site_name)
+    }
+
+    /// Name used as the author of posts, defaults to site_name
+    pub fn author_name(&self) -> &str {
+        self.author.as_ref().map(|a| a.name.as_str()).unwrap_or(&self.site_name)
+    }
+
+    pub fn author_url(&self) -> Option<&str> {
+        self.author.as_ref().and_then(|a| a.url.as_deref())
+    }
+
+    pub fn author_email(&self) -> Option<&str> {
+        self.author.as_ref().and_then(|a| a.email.as_deref())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthorConfig {
+    pub name: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -27,6 +61,23 @@ pub struct MicropubConfig {
     #[serde(default = "default_auth_token_endpoint")]
     pub auth_token_endpoint: String,
 
+    /// How access tokens are verified, `legacy` (a GET to auth_token_endpoint) or
+    /// `introspection` (RFC 7662, a POST to introspection_endpoint)
+    #[serde(default)]
+    pub token_verification: TokenVerification,
+
+    /// Discovered from host_website's indieauth-metadata if not set
+    #[serde(default)]
+    pub introspection_endpoint: Option<String>,
+
+    /// Authorization header value sent to the introspection endpoint, e.g. `Bearer <token>`
+    #[serde(default)]
+    pub introspection_authorization: Option<String>,
+
+    /// Longest time in seconds a token verification result is cached for, 0 disables caching
+    #[serde(default = "default_token_cache_max_ttl")]
+    pub token_cache_max_ttl: u64,
+
     pub host_website: String,
     pub media_endpoint: String,
     #[serde(default = "default_max_upload_length")]
@@ -35,6 +86,69 @@ pub struct MicropubConfig {
 
     #[serde(deserialize_with="offset_deserialize::deserialize")]
     pub current_timezone_offset: chrono::FixedOffset,
+
+    /// Syndication targets offered to clients via q=config and q=syndicate-to
+    #[serde(default)]
+    pub syndicate_to: Vec<SyndicationTarget>,
+
+    /// Built in IndieAuth server. When set, tokens are issued and verified by micropub-rs itself
+    /// and auth_endpoint/auth_token_endpoint are ignored.
+    #[serde(default)]
+    pub indieauth: Option<IndieAuthConfig>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenVerification {
+    #[default]
+    Legacy,
+    Introspection,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndieAuthConfig {
+    /// Secret entered on the consent page to approve a client's authorization request.
+    pub admin_credential: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SyndicationTarget {
+    pub uid: String,
+    pub name: String,
+}
+
+impl MicropubConfig {
+    /// Absolute URL of a path (e.g. a post slug) on host_website.
+    pub fn site_url(&self, path: &str) -> String {
+        format!(
+            "{}/{}",
+            self.host_website.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    }
+
+    /// Domain of host_website, e.g. for use in tag URIs.
+    pub fn site_domain(&self) -> Option<String> {
+        url::Url::parse(&self.host_website)
+            .ok()
+            .and_then(|u| u.host_str().map(String::from))
+    }
+
+    /// Authorization endpoint advertised to clients, the built in one if indieauth is configured.
+    pub fn authorization_endpoint(&self) -> String {
+        match self.indieauth {
+            Some(_) => self.site_url("auth"),
+            None => self.auth_endpoint.clone(),
+        }
+    }
+
+    /// Token endpoint advertised to clients, the built in one if indieauth is configured.
+    pub fn token_endpoint(&self) -> String {
+        match self.indieauth {
+            Some(_) => self.site_url("token"),
+            None => self.auth_token_endpoint.clone(),
+        }
+    }
 }
 
 fn default_auth_token_endpoint() -> String {
@@ -45,6 +159,10 @@ fn default_auth_endpoint() -> String {
     crate::DEFAULT_AUTH_ENDPOINT.into()
 }
 
+fn default_token_cache_max_ttl() -> u64 {
+    crate::DEFAULT_TOKEN_CACHE_MAX_TTL_SECONDS
+}
+
 fn default_max_upload_length() -> usize {
     crate::DEFAULT_MAX_CONTENT_LENGTH
 }
@@ -65,3 +183,55 @@ mod offset_deserialize {
         Ok(offset)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::MicropubConfig;
+
+    fn config(host_website: &str) -> MicropubConfig {
+        MicropubConfig {
+            auth_endpoint: crate::DEFAULT_AUTH_ENDPOINT.into(),
+            auth_token_endpoint: crate::DEFAULT_AUTH_TOKEN_ENDPOINT.into(),
+            token_verification: Default::default(),
+            introspection_endpoint: None,
+            introspection_authorization: None,
+            token_cache_max_ttl: crate::DEFAULT_TOKEN_CACHE_MAX_TTL_SECONDS,
+            host_website: host_website.into(),
+            media_endpoint: format!("{}media", host_website),
+            media_endpoint_max_upload_length: crate::DEFAULT_MAX_CONTENT_LENGTH,
+            micropub_endpoint: format!("{}micropub", host_website),
+            current_timezone_offset: chrono::FixedOffset::east_opt(0).unwrap(),
+            syndicate_to: vec![],
+            indieauth: None,
+        }
+    }
+
+    #[test]
+    fn site_url_joins_slug() {
+        assert_eq!(
+            config("https://example.com/").site_url("2020/10/24/testing"),
+            "https://example.com/2020/10/24/testing"
+        );
+        assert_eq!(
+            config("https://example.com").site_url("/media/abc"),
+            "https://example.com/media/abc"
+        );
+        assert_eq!(config("https://example.com/").site_url(""), "https://example.com/");
+    }
+
+    #[test]
+    fn site_domain_from_host_website() {
+        assert_eq!(config("https://example.com/").site_domain(), Some("example.com".into()));
+    }
+
+    #[test]
+    fn endpoints_use_builtin_indieauth_when_configured() {
+        let mut c = config("https://example.com/");
+        assert_eq!(c.authorization_endpoint(), crate::DEFAULT_AUTH_ENDPOINT);
+        assert_eq!(c.token_endpoint(), crate::DEFAULT_AUTH_TOKEN_ENDPOINT);
+
+        c.indieauth = Some(super::IndieAuthConfig { admin_credential: "secret".into() });
+        assert_eq!(c.authorization_endpoint(), "https://example.com/auth");
+        assert_eq!(c.token_endpoint(), "https://example.com/token");
+    }
+}
@@ -1,3 +1,4 @@
 pub const DEFAULT_MAX_CONTENT_LENGTH: usize = 1024 * 1024 * 50; // 50 megabytes
 pub const DEFAULT_AUTH_TOKEN_ENDPOINT: &str = "https://tokens.indieauth.com/token";
 pub const DEFAULT_AUTH_ENDPOINT: &str = "https://indieauth.com/auth";
+pub const DEFAULT_TOKEN_CACHE_MAX_TTL_SECONDS: u64 = 300; // 5 minutes
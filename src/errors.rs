@@ -1,103 +1,162 @@
+use axum::response::{IntoResponse, Json, Response};
 use http::StatusCode;
+use thiserror::Error;
 
-#[derive(Debug)]
-pub struct DBError {
-    not_found: bool,
+/// Errors produced while handling a request.
+///
+/// Micropub endpoints respond with these directly, producing the JSON error bodies described in
+/// https://micropub.spec.indieweb.org/#error-response. Handlers that render HTML convert them into
+/// a bare StatusCode instead.
+#[derive(Debug, Error)]
+pub enum MicropubError {
+    /// The request is missing a required parameter or a parameter has an invalid value.
+    #[error("{0}")]
+    InvalidRequest(String),
+
+    /// No access token was provided or the provided token could not be verified.
+    #[error("{0}")]
+    Unauthorized(String),
+
+    /// The token is valid but is not allowed to act on this site.
+    #[error("{0}")]
+    Forbidden(String),
+
+    /// The token is valid but was not granted the scope (named in the field) needed for the
+    /// request.
+    #[error("The access token does not have the '{0}' scope")]
+    InsufficientScope(&'static str),
+
+    /// An authorization code presented to the built in IndieAuth server is unknown, expired,
+    /// already used or doesn't match the request it was issued for.
+    #[error("{0}")]
+    InvalidGrant(String),
+
+    #[error("The requested resource was not found")]
+    NotFound,
+
+    /// Something went wrong on our end. The description is logged but not sent to the client.
+    #[error("{0}")]
+    Internal(String),
 }
-impl Default for DBError {
-    fn default() -> Self {
-        Self::new()
+
+impl MicropubError {
+    pub fn invalid_request<S: Into<String>>(description: S) -> Self {
+        Self::InvalidRequest(description.into())
     }
-}
-impl DBError {
-    pub fn new() -> Self {
-        DBError { not_found: false }
+
+    pub fn unauthorized<S: Into<String>>(description: S) -> Self {
+        Self::Unauthorized(description.into())
     }
 
-    pub fn not_found() -> Self {
-        DBError { not_found: true }
+    pub fn forbidden<S: Into<String>>(description: S) -> Self {
+        Self::Forbidden(description.into())
     }
-}
-impl From<DBError> for StatusCode {
-    fn from(e: DBError) -> Self {
-        if e.not_found {
-            StatusCode::NOT_FOUND
-        } else {
-            StatusCode::INTERNAL_SERVER_ERROR
+
+    pub fn invalid_grant<S: Into<String>>(description: S) -> Self {
+        Self::InvalidGrant(description.into())
+    }
+
+    pub fn internal<S: Into<String>>(description: S) -> Self {
+        Self::Internal(description.into())
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidRequest(_) | Self::InvalidGrant(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) | Self::InsufficientScope(_) => StatusCode::FORBIDDEN,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
-}
 
-#[derive(Debug)]
-pub struct ServerError;
-impl From<tokio::task::JoinError> for ServerError {
-    fn from(_e: tokio::task::JoinError) -> Self {
-        Self {}
+    /// The value of the `error` key in the response body.
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest(_) => "invalid_request",
+            Self::Unauthorized(_) => "unauthorized",
+            Self::Forbidden(_) => "forbidden",
+            Self::InsufficientScope(_) => "insufficient_scope",
+            Self::InvalidGrant(_) => "invalid_grant",
+            Self::NotFound => "not_found",
+            Self::Internal(_) => "server_error",
+        }
     }
 }
 
-impl From<ServerError> for StatusCode {
-    fn from(_e: ServerError) -> Self {
-        StatusCode::INTERNAL_SERVER_ERROR
+impl IntoResponse for MicropubError {
+    fn into_response(self) -> Response {
+        let description = match &self {
+            Self::Internal(_) => "An internal server error occurred".to_string(),
+            e => e.to_string(),
+        };
+        let mut body = json!({
+            "error": self.error_code(),
+            "error_description": description,
+        });
+        if let Self::InsufficientScope(scope) = &self {
+            body.as_object_mut()
+                .expect("is object")
+                .insert("scope".into(), json!(scope));
+        }
+
+        (self.status_code(), Json(body)).into_response()
     }
 }
 
-#[derive(Debug)]
-pub struct JSONSerializationError;
-
-#[derive(Debug)]
-pub struct TemplateError;
-impl From<TemplateError> for StatusCode {
-    fn from(_e: TemplateError) -> Self {
-        StatusCode::INTERNAL_SERVER_ERROR
+impl From<MicropubError> for StatusCode {
+    fn from(e: MicropubError) -> Self {
+        e.status_code()
     }
 }
 
-#[derive(Debug)]
-pub struct HTTPClientError;
-impl From<HTTPClientError> for StatusCode {
-    fn from(_e: HTTPClientError) -> Self {
-        StatusCode::INTERNAL_SERVER_ERROR
+impl From<diesel::result::Error> for MicropubError {
+    fn from(e: diesel::result::Error) -> Self {
+        crate::handler::handle_db_errors(e)
     }
 }
 
-#[derive(Debug)]
-pub struct ValidateResponseDeserializeError;
-impl From<ValidateResponseDeserializeError> for StatusCode {
-    fn from(_e: ValidateResponseDeserializeError) -> Self {
-        StatusCode::INTERNAL_SERVER_ERROR
+impl From<magick_rust::MagickError> for MicropubError {
+    fn from(e: magick_rust::MagickError) -> Self {
+        Self::Internal(format!("error stripping media: {}", e.0))
     }
 }
 
-#[derive(Debug)]
-pub struct NotAuthorized;
+#[cfg(test)]
+mod test {
+    use super::MicropubError;
+    use axum::response::IntoResponse;
+    use http::StatusCode;
 
-#[derive(Debug)]
-pub struct MediaUploadError;
-impl From<MediaUploadError> for StatusCode {
-    fn from(_e: MediaUploadError) -> Self {
-        StatusCode::INTERNAL_SERVER_ERROR
+    async fn body_json(e: MicropubError) -> (StatusCode, serde_json::Value) {
+        let response = e.into_response();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
     }
-}
 
-#[derive(Debug)]
-pub struct MediaFetchError;
-impl From<MediaFetchError> for StatusCode {
-    fn from(_e: MediaFetchError) -> Self {
-        StatusCode::INTERNAL_SERVER_ERROR
+    #[tokio::test]
+    async fn invalid_request_response() {
+        let (status, body) = body_json(MicropubError::invalid_request("missing 'url'")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, json!({"error": "invalid_request", "error_description": "missing 'url'"}));
     }
-}
 
-#[derive(Debug)]
-pub struct MediaStripError(#[allow(dead_code)] pub(crate) &'static str);
-impl From<MediaStripError> for StatusCode {
-    fn from(_e: MediaStripError) -> Self {
-        StatusCode::INTERNAL_SERVER_ERROR
+    #[tokio::test]
+    async fn insufficient_scope_response_includes_scope() {
+        let (status, body) = body_json(MicropubError::InsufficientScope("create")).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"], "insufficient_scope");
+        assert_eq!(body["scope"], "create");
     }
-}
 
-impl From<magick_rust::MagickError> for MediaStripError {
-    fn from(s: magick_rust::MagickError) -> Self {
-        Self(s.0)
+    #[tokio::test]
+    async fn internal_error_description_is_not_exposed() {
+        let (status, body) = body_json(MicropubError::internal("connection refused")).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["error"], "server_error");
+        assert_eq!(body["error_description"], "An internal server error occurred");
     }
 }
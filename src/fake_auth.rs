@@ -0,0 +1,160 @@
+//! A fake IndieAuth server for testing token verification. It is run on its own by the
+//! fake_auth_server binary, and in-process by tests with [`spawn`].
+//!
+//! Every token is valid, except `inactive_token` for introspection.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json},
+    routing::{get, post},
+    Form,
+    Router,
+};
+
+use crate::auth::{IntrospectionResponse, TokenValidateResponse};
+
+#[derive(Clone, Debug)]
+pub struct FakeAuthConfig {
+    /// The 'me' field in auth responses
+    pub me: String,
+    /// The 'client_id' field in auth responses
+    pub client_id: String,
+    /// The 'scope' field in auth responses
+    pub scope: String,
+    /// Token that POST /introspect reports as not active
+    pub inactive_token: String,
+    /// Seconds until tokens expire, reported as 'exp' by POST /introspect
+    pub expires_in: Option<i64>,
+    /// Number of token verification requests to answer with 500 Internal Server Error before
+    /// verifying tokens, to test the endpoint being unavailable
+    pub fail_requests: usize,
+}
+
+struct FakeAuthState {
+    config: FakeAuthConfig,
+    failures_left: AtomicUsize,
+}
+
+impl FakeAuthState {
+    /// Whether to fail this request, counting down fail_requests.
+    fn fail(&self) -> bool {
+        self.failures_left
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+    }
+}
+
+impl Default for FakeAuthConfig {
+    fn default() -> Self {
+        Self {
+            me: "https://example.com".into(),
+            client_id: "https://test-client.example.com".into(),
+            scope: "create update delete".into(),
+            inactive_token: "inactive".into(),
+            expires_in: None,
+            fail_requests: 0,
+        }
+    }
+}
+
+/// Routes for the home page (linking to the metadata), the IndieAuth metadata, GET /token and
+/// POST /introspect.
+pub fn router(config: FakeAuthConfig) -> Router {
+    Router::new()
+        .route("/", get(home_page))
+        .route("/.well-known/oauth-authorization-server", get(metadata))
+        .route("/token", get(validate_token))
+        .route("/introspect", post(introspect_token))
+        .with_state(Arc::new(FakeAuthState {
+            failures_left: AtomicUsize::new(config.fail_requests),
+            config,
+        }))
+}
+
+/// Serves the fake server on a free local port in the background, returning its url (with a
+/// trailing slash).
+pub async fn spawn(config: FakeAuthConfig) -> std::io::Result<String> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}/", listener.local_addr()?);
+    let app = router(config);
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    Ok(url)
+}
+
+async fn validate_token(State(state): State<Arc<FakeAuthState>>) -> Result<Json<TokenValidateResponse>, StatusCode> {
+    if state.fail() {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    // Return a fake but valid token validation response
+    let config = &state.config;
+    Ok(Json(TokenValidateResponse {
+        me: config.me.clone(),
+        client_id: config.client_id.clone(),
+        issued_at: 1640995200, // 2022-01-01 00:00:00 UTC
+        scope: config.scope.clone(),
+        nonce: 12345,
+        exp: None,
+    }))
+}
+
+async fn introspect_token(
+    State(state): State<Arc<FakeAuthState>>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<Json<IntrospectionResponse>, StatusCode> {
+    if state.fail() {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let config = &state.config;
+    let token = form.get("token").map(String::as_str).unwrap_or_default();
+    if token.is_empty() || token == config.inactive_token {
+        return Ok(Json(IntrospectionResponse {
+            active: false,
+            me: None,
+            client_id: None,
+            scope: None,
+            exp: None,
+            iat: None,
+        }));
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    Ok(Json(IntrospectionResponse {
+        active: true,
+        me: Some(config.me.clone()),
+        client_id: Some(config.client_id.clone()),
+        scope: Some(config.scope.clone()),
+        exp: config.expires_in.map(|e| now + e),
+        iat: Some(now),
+    }))
+}
+
+/// A home page advertising the metadata endpoint, for testing discovery with host_website
+/// pointed at this server.
+async fn home_page() -> impl IntoResponse {
+    (
+        [(header::LINK, r#"</.well-known/oauth-authorization-server>; rel="indieauth-metadata""#)],
+        "<html><head></head><body>fake auth server</body></html>",
+    )
+}
+
+/// Endpoint urls are built from the Host the request was sent to, so they're right whichever
+/// port the server is listening on.
+async fn metadata(headers: HeaderMap) -> Json<serde_json::Value> {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("127.0.0.1");
+    let base = format!("http://{}", host);
+    Json(json!({
+        "issuer": format!("{}/", base),
+        "token_endpoint": format!("{}/token", base),
+        "introspection_endpoint": format!("{}/introspect", base),
+    }))
+}
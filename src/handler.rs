@@ -5,13 +5,13 @@ use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
 use diesel::SqliteConnection;
 use log::error;
 
-use crate::errors::DBError;
+use crate::errors::MicropubError;
 
-pub fn handle_db_errors(e: diesel::result::Error) -> DBError {
+pub fn handle_db_errors(e: diesel::result::Error) -> MicropubError {
     error!("{:?}", e);
     match e {
-        diesel::result::Error::NotFound => DBError::not_found(),
-        _ => DBError::new(),
+        diesel::result::Error::NotFound => MicropubError::NotFound,
+        _ => MicropubError::internal("database error"),
     }
 }
 
@@ -19,24 +19,20 @@ pub trait WithDB {
     fn dbpool(&self) -> &Pool<ConnectionManager<SqliteConnection>>;
 
     // TODO refactor all calls to this function over to handle_db_errors
-    fn handle_errors(&self, e: diesel::result::Error) -> DBError {
-        error!("{:?}", e);
-        match e {
-            diesel::result::Error::NotFound => DBError::not_found(),
-            _ => DBError::new(),
-        }
+    fn handle_errors(&self, e: diesel::result::Error) -> MicropubError {
+        handle_db_errors(e)
     }
 
     #[tracing::instrument(level = "debug", skip(self))]
-    fn dbconn(&self) -> Result<PooledConnection<ConnectionManager<SqliteConnection>>, DBError> {
+    fn dbconn(&self) -> Result<PooledConnection<ConnectionManager<SqliteConnection>>, MicropubError> {
         self.dbpool().get().map_err(|e| {
             error!("error getting connection: {:?}", e);
-            DBError::new()
+            MicropubError::internal("error getting database connection")
         })
     }
 
     #[tracing::instrument(level = "debug", skip(self, f))]
-    fn run_txn<T, F>(&self, f: F) -> Result<T, DBError>
+    fn run_txn<T, F>(&self, f: F) -> Result<T, MicropubError>
     where
         F: FnOnce(
             &mut PooledConnection<ConnectionManager<SqliteConnection>>,
@@ -1,11 +1,20 @@
 mod archive;
 mod atom;
+mod events;
 mod fetch;
 mod index;
+mod indieauth;
 pub mod micropub;
+mod webmention;
 
 pub use archive::get_archive_handler;
 pub use atom::get_atom_handler; 
-pub use fetch::{get_media_handler, get_post_handler};
+pub use events::get_events_handler;
+pub use fetch::{get_media_handler, get_post_handler, get_preview_handler};
 pub use index::get_index_handler;
+pub use indieauth::{
+    get_authorization_handler, get_metadata_handler, get_token_handler,
+    post_authorization_handler, post_revoke_handler, post_token_handler,
+};
 pub use micropub::{handle_media_upload, handle_post, handle_query};
+pub use webmention::{handle_webmention, Verifications};
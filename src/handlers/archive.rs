@@ -26,7 +26,7 @@ pub async fn get_archive_handler(
     let mut conn = db.dbconn()?;
     let posts = tag_ref
         .map(|t| Post::by_tag(t))
-        .unwrap_or(Post::all())
+        .unwrap_or(Post::public())
         .load::<Post>(&mut conn)
         .map_err(|e| db.handle_errors(e))?;
 
@@ -49,9 +49,7 @@ pub async fn get_archive_handler(
         // handled e.g. at the view model creation time.
         let datetime = post_util::get_local_datetime(&post.created_at, &site_config.micropub.current_timezone_offset).map_err(|e| {
             error!("date parsing error: {:?}", e);
-            // TODO shouldn't be a template error but realistically this would only happen if
-            // the DB had malformed data for template rendering...
-            TemplateError
+            MicropubError::internal("malformed post date")
         })?;
         post.created_at = datetime.to_rfc3339();
 
@@ -71,7 +69,7 @@ pub async fn get_archive_handler(
         .add_context("tag", &tag_ref);
     let page = template.render("archives.html").map_err(|e| {
         error!("{:?}", e);
-        TemplateError
+        MicropubError::internal("template rendering failed")
     })?;
 
     Ok(Html(page))
@@ -7,10 +7,10 @@ use log::error;
 
 use crate::errors::*;
 use crate::handler::{MicropubDB, WithDB};
-use crate::models::Post;
+use crate::models::{Location, Post, PostObject, Review};
 use crate::post_util;
 use crate::templates;
-use crate::view_models::{Date as DateView, Post as PostView};
+use crate::view_models::{Date as DateView, NestedObject, Post as PostView, Review as ReviewView};
 
 use axum::response::IntoResponse;
 use http::{header, StatusCode};
@@ -23,7 +23,7 @@ pub async fn get_atom_handler(
     let db = MicropubDB::new(pool);
     let mut conn = db.dbconn()?;
 
-    let posts = Post::all()
+    let posts = Post::public()
         .load::<Post>(&mut conn)
         .map_err(|e: diesel::result::Error| db.handle_errors(e))?;
 
@@ -53,24 +53,57 @@ pub async fn get_atom_handler(
         photos_by_post.entry(post_id_).or_default().push((url, alt));
     }
 
+    let mut reviews_by_post: HashMap<i32, Review> = Review::for_posts(&post_ids)
+        .load::<Review>(&mut conn)
+        .map_err(|e| db.handle_errors(e))?
+        .into_iter()
+        .map(|r| (r.post_id, r))
+        .collect();
+
+    let mut objects_by_post: HashMap<i32, Vec<NestedObject>> = HashMap::new();
+    let post_objects = PostObject::for_posts(&post_ids)
+        .load::<PostObject>(&mut conn)
+        .map_err(|e| db.handle_errors(e))?;
+    for object in post_objects {
+        let pid = object.post_id;
+        let nested = NestedObject::try_from(object).map_err(|e| {
+            error!("malformed post object: {:?}", e);
+            MicropubError::internal("malformed post object")
+        })?;
+        objects_by_post.entry(pid).or_default().push(nested);
+    }
+
+    let mut locations_by_post: HashMap<i32, Vec<Location>> = HashMap::new();
+    for location in Location::for_posts(&post_ids)
+        .load::<Location>(&mut conn)
+        .map_err(|e| db.handle_errors(e))?
+    {
+        locations_by_post.entry(location.post_id).or_default().push(location);
+    }
+
     for mut post in posts {
         // TODO this is copied from FetchHandler. Both should not do this and should instead be
         // handled e.g. at the view model creation time.
         let datetime = post_util::get_local_datetime(&post.created_at, &site_config.micropub.current_timezone_offset).map_err(|e| {
             error!("date parsing error: {:?}", e);
-            // TODO shouldn't be a template error but realistically this would only happen if
-            // the DB had malformed data for template rendering...
-            TemplateError
+            MicropubError::internal("malformed post date")
         })?;
         post.created_at = datetime.to_rfc3339();
 
         let pid = post.id;
-        let post_view = PostView::new_from(
+        let mut post_view = PostView::new_from(
             post,
             tags.remove(&pid).unwrap_or_default(),
             DateView::from(&datetime),
             photos_by_post.remove(&pid).unwrap_or_default(),
         );
+        if let Some(r) = reviews_by_post.remove(&pid) {
+            post_view = post_view.with_review(ReviewView::from(r));
+        }
+        post_view = post_view.with_objects(objects_by_post.remove(&pid).unwrap_or_default());
+        for location in locations_by_post.remove(&pid).unwrap_or_default() {
+            post_view = post_view.with_location(location);
+        }
         posts_views.push(post_view);
     }
 
@@ -85,7 +118,7 @@ pub async fn get_atom_handler(
         .add_context("posts", &posts_views);
     let feed = template.render("atom.xml").map_err(|e| {
         error!("{:?}", e);
-        TemplateError
+        MicropubError::internal("template rendering failed")
     })?;
 
     Ok((StatusCode::OK, [(header::CONTENT_TYPE, "text/xml")], feed))
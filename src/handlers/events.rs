@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::response::{Html, IntoResponse};
+use diesel::prelude::*;
+use diesel::r2d2;
+use http::StatusCode;
+use log::error;
+
+use crate::errors::*;
+use crate::handler::{MicropubDB, WithDB};
+use crate::models::{Event, Post};
+use crate::post_util;
+use crate::templates;
+use crate::view_models::{Date as DateView, Event as EventView, Post as PostView};
+
+/// Lists h-event posts, split into events that have not yet finished (soonest first) and past
+/// events (most recent first).
+pub async fn get_events_handler(
+    pool: Arc<r2d2::Pool<r2d2::ConnectionManager<SqliteConnection>>>,
+    templates: Arc<templates::Templates>,
+    site_config: Arc<crate::MicropubSiteConfig>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let db = MicropubDB::new(pool);
+    let mut conn = db.dbconn()?;
+
+    let events = Event::all()
+        .load::<Event>(&mut conn)
+        .map_err(|e| db.handle_errors(e))?;
+    let post_ids: Vec<i32> = events.iter().map(|e| e.post_id).collect();
+    let mut posts: HashMap<i32, Post> = Post::public()
+        .filter(crate::schema::posts::id.eq_any(&post_ids))
+        .load::<Post>(&mut conn)
+        .map_err(|e| db.handle_errors(e))?
+        .into_iter()
+        .map(|p| (p.id, p))
+        .collect();
+
+    let offset = &site_config.micropub.current_timezone_offset;
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let mut upcoming = vec![];
+    let mut past = vec![];
+    for event in events {
+        let Some(mut post) = posts.remove(&event.post_id) else {
+            continue;
+        };
+        let is_upcoming = event.is_upcoming(&now);
+
+        let datetime = post_util::get_local_datetime(&post.created_at, offset).map_err(|e| {
+            error!("date parsing error: {:?}", e);
+            MicropubError::internal("malformed post date")
+        })?;
+        post.created_at = datetime.to_rfc3339();
+        let event_view = EventView::new_from(event, offset).map_err(|e| {
+            error!("event date parsing error: {:?}", e);
+            MicropubError::internal("malformed event date")
+        })?;
+
+        let post_view = PostView::new_from(post, vec![], DateView::from(&datetime), vec![])
+            .with_event(event_view);
+        if is_upcoming {
+            upcoming.push(post_view);
+        } else {
+            past.push(post_view);
+        }
+    }
+    // events are loaded most recent first, upcoming events read better soonest first
+    upcoming.reverse();
+
+    let page = templates
+        .add_context("upcoming", &upcoming)
+        .add_context("past", &past)
+        .render("events.html")
+        .map_err(|e| {
+            error!("{:?}", e);
+            MicropubError::internal("template rendering failed")
+        })?;
+
+    Ok(Html(page))
+}
@@ -2,44 +2,168 @@ use std::sync::Arc;
 
 use axum::{
     extract::Path,
-    response::{Html, IntoResponse},
+    response::{Html, IntoResponse, Response},
 };
 use bytes::Bytes;
 use diesel::prelude::*;
 use diesel::r2d2;
 use futures::join;
-use http::StatusCode;
-use tracing::{debug, error, Instrument, debug_span};
+use http::{header, HeaderMap, HeaderValue, StatusCode};
+use tracing::{debug, error, warn, Instrument, debug_span};
 
+use crate::auth::TokenCache;
 use crate::errors::*;
 use crate::handler::{handle_db_errors, MicropubDB, WithDB};
-use crate::models::Post;
+use crate::handlers::micropub::authorize_token;
+use crate::models::{Event, Location, Mention, Post, PostObject, Review};
 use crate::post_util;
 use crate::templates;
-use crate::view_models::{Date as DateView, Post as PostView};
+use crate::view_models::{Date as DateView, Event as EventView, NestedObject, Post as PostView, Review as ReviewView};
 
-#[tracing::instrument(level = "info", skip(pool, templates, site_config))]
+/// Renders the post at a slug. Private posts are only shown to viewers with an access token for
+/// the site, given either as a bearer token or the `token` query parameter.
+#[tracing::instrument(level = "info", skip(headers, token, http_client, pool, token_cache, templates, site_config))]
+#[allow(clippy::too_many_arguments)]
 pub async fn get_post_handler(
     url_slug: String,
+    headers: HeaderMap,
+    token: Option<String>,
+    http_client: reqwest::Client,
     pool: Arc<r2d2::Pool<r2d2::ConnectionManager<SqliteConnection>>>,
+    token_cache: Arc<TokenCache>,
     templates: Arc<templates::Templates>,
     site_config: Arc<crate::MicropubSiteConfig>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<Response, StatusCode> {
     let db = MicropubDB::new(pool);
-    let mut conn = db.dbconn()?;
 
+    let auth = match headers.get(header::AUTHORIZATION).and_then(|h| h.to_str().ok()) {
+        Some(h) => Some(h.to_string()),
+        None => token.map(|t| format!("Bearer {}", t)),
+    };
     let slug_clone = url_slug.clone();
     let mut slug_conn = db.dbconn()?;
-    let mut post: Post = 
+    let maybe_post: Option<Post> =
         tokio_rayon::spawn_fifo(move || {
-            Post::by_slug(&slug_clone)
+            Post::published_by_slug(&slug_clone, true)
                 .first::<Post>(&mut slug_conn)
+                .optional()
                 .map_err(handle_db_errors)
         })
         .instrument(debug_span!("post_by_slug"))
         .await?;
+    // The token is only verified, which may mean a request to the token endpoint, once a private
+    // post has been found.
+    let maybe_post = match maybe_post {
+        Some(post) if post.visibility == "private"
+            && !viewer_authorized(auth.as_deref(), &http_client, &site_config, &db, &token_cache).await => None,
+        post => post,
+    };
+
+    match maybe_post {
+        Some(post) => {
+            let webmention_link = format!("<{}>; rel=\"webmention\"", site_config.micropub.site_url("webmention"));
+            let mut response = render_post(post, &db, &templates, site_config).await?;
+            if let Ok(link) = HeaderValue::from_str(&webmention_link) {
+                response.headers_mut().insert(header::LINK, link);
+            }
+            Ok(response)
+        },
+        None => {
+            let mut conn = db.dbconn()?;
+            let authorized = viewer_authorized(auth.as_deref(), &http_client, &site_config, &db, &token_cache);
+            missing_post_response(&url_slug, authorized, &mut conn, &templates, &site_config).await
+        }
+    }
+}
+
+/// Whether the viewer's token is accepted by the site, allowing them to see private posts.
+async fn viewer_authorized(
+    auth: Option<&str>,
+    http_client: &reqwest::Client,
+    site_config: &Arc<crate::MicropubSiteConfig>,
+    db: &MicropubDB,
+    token_cache: &TokenCache,
+) -> bool {
+    match auth {
+        Some(auth) => authorize_token(http_client.clone(), site_config.clone(), db, token_cache, auth)
+            .await
+            .map_err(|e| warn!("not showing private posts, token was not accepted: {:?}", e))
+            .is_ok(),
+        None => false,
+    }
+}
+
+/// Renders a post whatever its post-status, published date or visibility, so that drafts and
+/// scheduled posts can be checked before they go live. Requires an access token for the site, given as the
+/// `token` query parameter.
+#[tracing::instrument(level = "info", skip(token, http_client, pool, token_cache, templates, site_config))]
+pub async fn get_preview_handler(
+    url_slug: String,
+    token: Option<String>,
+    http_client: reqwest::Client,
+    pool: Arc<r2d2::Pool<r2d2::ConnectionManager<SqliteConnection>>>,
+    token_cache: Arc<TokenCache>,
+    templates: Arc<templates::Templates>,
+    site_config: Arc<crate::MicropubSiteConfig>,
+) -> Result<Response, StatusCode> {
+    let token = token.ok_or_else(|| MicropubError::unauthorized("missing access token"))?;
+    let db = MicropubDB::new(pool);
+    authorize_token(http_client, site_config.clone(), &db, &token_cache, &format!("Bearer {}", token)).await?;
+
+    let mut conn = db.dbconn()?;
+    let post = Post::by_slug(&url_slug)
+        .first::<Post>(&mut conn)
+        .optional()
+        .map_err(handle_db_errors)?
+        .ok_or(StatusCode::NOT_FOUND)?;
 
+    render_post(post, &db, &templates, site_config).await
+}
+
+async fn render_post(
+    mut post: Post,
+    db: &MicropubDB,
+    templates: &templates::Templates,
+    site_config: Arc<crate::MicropubSiteConfig>,
+) -> Result<Response, StatusCode> {
+    let mut conn = db.dbconn()?;
     let post_id = post.id;
+    let event = if post.entry_type == "event" {
+        let post_ids = [post_id];
+        Event::for_posts(&post_ids)
+            .first::<Event>(&mut conn)
+            .optional()
+            .map_err(handle_db_errors)?
+    } else {
+        None
+    };
+    let review = if post.entry_type == "review" {
+        let post_ids = [post_id];
+        Review::for_posts(&post_ids)
+            .first::<Review>(&mut conn)
+            .optional()
+            .map_err(handle_db_errors)?
+    } else {
+        None
+    };
+    let post_ids = [post_id];
+    let objects = PostObject::for_posts(&post_ids)
+        .load::<PostObject>(&mut conn)
+        .map_err(handle_db_errors)?
+        .into_iter()
+        .map(NestedObject::try_from)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| {
+            error!("malformed post object: {:?}", e);
+            MicropubError::internal("malformed post object")
+        })?;
+    let locations = Location::for_posts(&post_ids)
+        .load::<Location>(&mut conn)
+        .map_err(handle_db_errors)?;
+    let mentions = Mention::verified_for_posts(&post_ids)
+        .load::<Mention>(&mut conn)
+        .map_err(handle_db_errors)?;
+
     let mut tags_conn = db.dbconn()?;
     let tags_fut =
         tokio_rayon::spawn_fifo(move || {
@@ -70,18 +194,30 @@ pub async fn get_post_handler(
     debug!("input datetime: {:?}", post.created_at);
     let datetime = post_util::get_local_datetime(&post.created_at, &site_config.micropub.current_timezone_offset).map_err(|e| {
         error!("date parsing error: {:?}", e);
-        // TODO shouldn't be a template error but realistically this would only happen if
-        // the DB had malformed data for template rendering...
-        TemplateError
+        MicropubError::internal("malformed post date")
     })?;
     post.created_at = datetime.to_rfc3339();
 
-    let post_view =
+    let mut post_view =
         tokio_rayon::spawn_fifo(move || {
             PostView::new_from(post, tags, DateView::from(&datetime), photos)
         })
         .instrument(debug_span!("create post view model"))
         .await;
+    if let Some(e) = event {
+        let event_view = EventView::new_from(e, &site_config.micropub.current_timezone_offset).map_err(|e| {
+            error!("event date parsing error: {:?}", e);
+            MicropubError::internal("malformed event date")
+        })?;
+        post_view = post_view.with_event(event_view);
+    }
+    if let Some(r) = review {
+        post_view = post_view.with_review(ReviewView::from(r));
+    }
+    let post_view = locations
+        .into_iter()
+        .fold(post_view.with_objects(objects), PostView::with_location);
+    let post_view = mentions.into_iter().fold(post_view, PostView::with_mention);
 
     let _templates = debug_span!("template_render");
     _templates.in_scope(|| {
@@ -90,12 +226,47 @@ pub async fn get_post_handler(
             .render("article.html")
             .map_err(|e| {
                 error!("{:?}", e);
-                TemplateError
+                MicropubError::internal("template rendering failed")
             })?;
-        Ok(Html(page))
+        Ok(Html(page).into_response())
     })
 }
 
+/// Responds with 410 Gone if a post previously existed at the slug but has been deleted, a 301 to
+/// the current slug if the post has been renamed, otherwise 404 Not Found. Renamed drafts,
+/// scheduled posts and (unless `authorized` resolves to true) private posts aren't redirected to.
+async fn missing_post_response(
+    url_slug: &str,
+    authorized: impl Future<Output = bool>,
+    conn: &mut SqliteConnection,
+    templates: &templates::Templates,
+    site_config: &crate::MicropubSiteConfig,
+) -> Result<Response, StatusCode> {
+    let deleted_post: Option<Post> = Post::deleted_by_slug(url_slug)
+        .first::<Post>(conn)
+        .optional()
+        .map_err(handle_db_errors)?;
+    let Some(deleted_post) = deleted_post else {
+        let renamed_post: Post = Post::redirected_from(url_slug, true)
+            .first::<Post>(conn)
+            .map_err(handle_db_errors)?;
+        if renamed_post.visibility == "private" && !authorized.await {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        let location = site_config.micropub.site_url(&renamed_post.slug);
+        return Ok((StatusCode::MOVED_PERMANENTLY, [(header::LOCATION, location)]).into_response());
+    };
+
+    // A gone.html template is optional, fall back to an empty 410 if the theme doesn't have one.
+    match templates.add_context("slug", &deleted_post.slug).render("gone.html") {
+        Ok(page) => Ok((StatusCode::GONE, Html(page)).into_response()),
+        Err(e) => {
+            debug!("not rendering gone.html: {:?}", e);
+            Ok(StatusCode::GONE.into_response())
+        }
+    }
+}
+
 #[tracing::instrument(level = "info", skip(pool, client, blobject_store_base_uri))]
 pub async fn get_media_handler(
     Path(media_id): Path<String>,
@@ -110,7 +281,7 @@ pub async fn get_media_handler(
         .await
         .map_err(|e| {
             error!("error in GET to rustyblobjectstore: {:?}", e);
-            MediaFetchError
+            MicropubError::internal("error fetching media")
         })?;
 
     use crate::schema::media::dsl::*;
@@ -129,7 +300,7 @@ pub async fn get_media_handler(
             .instrument(debug_span!("blobject store resp get"))
             .await.map_err(|e| {
                 error!("error in receiving body as bytes(): {:?}", e);
-                MediaFetchError
+                MicropubError::internal("error fetching media")
             })?;
         Ok((
             StatusCode::OK,
@@ -42,15 +42,13 @@ pub async fn get_index_handler(
     // Only on main page for indieauth login
     let template = templates
         .add_context("SOCIAL", &site_config.site.socials)
-        .add_context("AUTH_ENDPOINT", &site_config.micropub.auth_endpoint)
-        .add_context("TOKEN_ENDPOINT", &site_config.micropub.auth_token_endpoint)
+        .add_context("AUTH_ENDPOINT", &site_config.micropub.authorization_endpoint())
+        .add_context("TOKEN_ENDPOINT", &site_config.micropub.token_endpoint())
         .add_context("MICROPUB_ENDPOINT", &site_config.micropub.micropub_endpoint);
 
     let datetime = post_util::get_local_datetime(&post.created_at, &site_config.micropub.current_timezone_offset).map_err(|e| {
         error!("date parsing error: {:?}", e);
-        // TODO shouldn't be a template error but realistically this would only happen if
-        // the DB had malformed data for template rendering...
-        TemplateError
+        MicropubError::internal("malformed post date")
     })?;
     post.created_at = datetime.to_rfc3339();
 
@@ -64,7 +62,7 @@ pub async fn get_index_handler(
         .render("index.html")
         .map_err(|e| {
             error!("{:?}", e);
-            TemplateError
+            MicropubError::internal("template rendering failed")
         })?;
 
     Ok(Html(page))
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Form, Query},
+    response::{Html, IntoResponse, Json, Response},
+};
+use diesel::prelude::*;
+use http::{header, HeaderMap, StatusCode};
+use log::{error, info, warn};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use crate::auth::TokenValidateResponse;
+use crate::errors::*;
+use crate::handler::{MicropubDB, WithDB};
+use crate::indieauth::{self, AuthorizationRequest, AUTH_CODE_LIFETIME_MINUTES};
+use crate::models::{AuthCode, NewAccessToken, NewAuthCode};
+use crate::schema::{access_tokens, auth_codes};
+use crate::templates;
+
+const DB_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Form submitted from the consent page.
+#[derive(Debug, Deserialize)]
+struct ConsentForm {
+    #[serde(flatten)]
+    request: AuthorizationRequest,
+    credential: String,
+}
+
+/// An authorization code exchanged at the authorization endpoint (for the profile only) or the
+/// token endpoint (for an access token).
+#[derive(Debug, Deserialize)]
+struct CodeRedemption {
+    grant_type: String,
+    code: String,
+    client_id: String,
+    redirect_uri: String,
+    code_verifier: String,
+}
+
+/// Shows the consent page for an authorization request.
+#[tracing::instrument(level = "info", skip(templates))]
+pub async fn get_authorization_handler(
+    Query(request): Query<AuthorizationRequest>,
+    templates: Arc<templates::Templates>,
+) -> Result<Response, MicropubError> {
+    request.validate().map_err(MicropubError::invalid_request)?;
+    render_consent(&templates, &request, None, StatusCode::OK)
+}
+
+/// Handles both the consent page form, redirecting back to the client with an authorization code
+/// if the admin credential is correct, and redemption of codes issued without any scope, which
+/// only confirm the user's identity.
+#[tracing::instrument(level = "info", skip(params, db, templates, site_config))]
+pub async fn post_authorization_handler(
+    Form(params): Form<HashMap<String, String>>,
+    db: Arc<MicropubDB>,
+    templates: Arc<templates::Templates>,
+    site_config: Arc<crate::MicropubSiteConfig>,
+) -> Result<Response, MicropubError> {
+    if params.contains_key("grant_type") {
+        redeem_code(&db, &from_params(&params)?)?;
+        return Ok(Json(json!({"me": site_config.micropub.host_website})).into_response());
+    }
+
+    let consent: ConsentForm = from_params(&params)?;
+    let request = consent.request;
+    request.validate().map_err(MicropubError::invalid_request)?;
+
+    let indieauth_config = site_config.micropub.indieauth.as_ref().ok_or(MicropubError::NotFound)?;
+    if !indieauth::credential_matches(&consent.credential, &indieauth_config.admin_credential) {
+        warn!("rejecting authorization request from {:?}, incorrect credential", request.client_id);
+        return render_consent(
+            &templates,
+            &request,
+            Some("The credential was not correct."),
+            StatusCode::FORBIDDEN,
+        );
+    }
+
+    let mut location = url::Url::parse(&request.redirect_uri)
+        .map_err(|_| MicropubError::invalid_request("redirect_uri is not a url"))?;
+    let code = indieauth::random_token();
+    let code_hash = indieauth::token_hash(&code);
+    let expires_at = (chrono::Utc::now() + chrono::Duration::minutes(AUTH_CODE_LIFETIME_MINUTES))
+        .format(DB_DATETIME_FORMAT)
+        .to_string();
+    let mut conn = db.dbconn()?;
+    diesel::insert_into(auth_codes::table)
+        .values(NewAuthCode {
+            code_hash: &code_hash,
+            client_id: &request.client_id,
+            redirect_uri: &request.redirect_uri,
+            scope: &request.scope,
+            code_challenge: &request.code_challenge,
+            expires_at: &expires_at,
+        })
+        .execute(&mut conn)?;
+    info!("issued authorization code to {:?} with scope {:?}", request.client_id, request.scope);
+
+    location
+        .query_pairs_mut()
+        .append_pair("code", &code)
+        .append_pair("state", &request.state)
+        .append_pair("iss", &site_config.micropub.site_url(""));
+    Ok((StatusCode::FOUND, [(header::LOCATION, location.to_string())]).into_response())
+}
+
+/// Exchanges an authorization code for an access token, or revokes a token when called with
+/// `action=revoke`.
+#[tracing::instrument(level = "info", skip(params, db, site_config))]
+pub async fn post_token_handler(
+    Form(params): Form<HashMap<String, String>>,
+    db: Arc<MicropubDB>,
+    site_config: Arc<crate::MicropubSiteConfig>,
+) -> Result<Response, MicropubError> {
+    if params.get("action").map(String::as_str) == Some("revoke") {
+        return revoke_token(&db, params.get("token"));
+    }
+
+    let auth_code = redeem_code(&db, &from_params(&params)?)?;
+    if auth_code.scope.is_empty() {
+        return Err(MicropubError::invalid_grant(
+            "the authorization code was issued without any scope and can't be used for an access token",
+        ));
+    }
+
+    let token = indieauth::random_token();
+    let token_hash = indieauth::token_hash(&token);
+    let mut conn = db.dbconn()?;
+    diesel::insert_into(access_tokens::table)
+        .values(NewAccessToken {
+            token_hash: &token_hash,
+            client_id: &auth_code.client_id,
+            scope: &auth_code.scope,
+        })
+        .execute(&mut conn)?;
+    info!("issued access token to {:?} with scope {:?}", auth_code.client_id, auth_code.scope);
+
+    Ok(Json(json!({
+        "access_token": token,
+        "token_type": "Bearer",
+        "scope": auth_code.scope,
+        "me": site_config.micropub.host_website,
+    })).into_response())
+}
+
+/// Verifies the bearer token in the Authorization header, responding in the same format as
+/// external token endpoints.
+#[tracing::instrument(level = "info", skip(headers, db, site_config))]
+pub async fn get_token_handler(
+    headers: HeaderMap,
+    db: Arc<MicropubDB>,
+    site_config: Arc<crate::MicropubSiteConfig>,
+) -> Result<Json<TokenValidateResponse>, MicropubError> {
+    let auth = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| MicropubError::unauthorized("missing access token"))?;
+
+    let mut conn = db.dbconn()?;
+    indieauth::validate_token(&mut conn, &site_config.micropub, auth)?
+        .map(Json)
+        .ok_or_else(|| MicropubError::forbidden("the access token could not be verified"))
+}
+
+/// Revokes the access token in the `token` parameter (RFC 7009).
+#[tracing::instrument(level = "info", skip(params, db))]
+pub async fn post_revoke_handler(
+    Form(params): Form<HashMap<String, String>>,
+    db: Arc<MicropubDB>,
+) -> Result<Response, MicropubError> {
+    revoke_token(&db, params.get("token"))
+}
+
+/// IndieAuth server metadata, served at /.well-known/oauth-authorization-server.
+pub async fn get_metadata_handler(site_config: Arc<crate::MicropubSiteConfig>) -> Json<serde_json::Value> {
+    let config = &site_config.micropub;
+    Json(json!({
+        "issuer": config.site_url(""),
+        "authorization_endpoint": config.authorization_endpoint(),
+        "token_endpoint": config.token_endpoint(),
+        "revocation_endpoint": config.site_url("revoke"),
+        "response_types_supported": ["code"],
+        "grant_types_supported": ["authorization_code"],
+        "code_challenge_methods_supported": ["S256"],
+        "scopes_supported": ["create", "update", "delete", "media"],
+    }))
+}
+
+fn render_consent(
+    templates: &templates::Templates,
+    request: &AuthorizationRequest,
+    error: Option<&str>,
+    status: StatusCode,
+) -> Result<Response, MicropubError> {
+    let scopes: Vec<&str> = request.scope.split_whitespace().collect();
+    let page = templates
+        .add_context("request", request)
+        .add_context("scopes", &scopes)
+        .add_context("error", &error)
+        .render("consent.html")
+        .map_err(|e| {
+            error!("{:?}", e);
+            MicropubError::internal("template rendering failed")
+        })?;
+    Ok((status, Html(page)).into_response())
+}
+
+/// Marks an unused, unexpired authorization code as used and checks that it was issued for the
+/// client, redirect_uri and PKCE code_verifier it is presented with. A code can only be redeemed
+/// once, even if the checks fail.
+fn redeem_code(db: &MicropubDB, redemption: &CodeRedemption) -> Result<AuthCode, MicropubError> {
+    if redemption.grant_type != "authorization_code" {
+        return Err(MicropubError::invalid_request(format!(
+            "unsupported grant_type '{}'",
+            redemption.grant_type
+        )));
+    }
+
+    let now = chrono::Utc::now().format(DB_DATETIME_FORMAT).to_string();
+    let code_hash = indieauth::token_hash(&redemption.code);
+    let auth_code = db
+        .run_txn(|conn| {
+            let auth_code = AuthCode::redeemable(&code_hash, &now)
+                .first::<AuthCode>(conn)
+                .optional()?;
+            if let Some(auth_code) = &auth_code {
+                diesel::update(auth_codes::table.find(auth_code.id))
+                    .set(auth_codes::used_at.eq(&now))
+                    .execute(conn)?;
+            }
+            Ok(auth_code)
+        })?
+        .ok_or_else(|| {
+            warn!("authorization code is unknown, expired or already used");
+            MicropubError::invalid_grant("the authorization code is invalid or has expired")
+        })?;
+
+    if auth_code.client_id != redemption.client_id || auth_code.redirect_uri != redemption.redirect_uri {
+        warn!(
+            "authorization code issued to {:?} presented by {:?}",
+            auth_code.client_id, redemption.client_id
+        );
+        return Err(MicropubError::invalid_grant(
+            "the authorization code was not issued for this client_id and redirect_uri",
+        ));
+    }
+    if !indieauth::verify_pkce(&redemption.code_verifier, &auth_code.code_challenge) {
+        return Err(MicropubError::invalid_grant("the code_verifier does not match the code_challenge"));
+    }
+
+    Ok(auth_code)
+}
+
+fn revoke_token(db: &MicropubDB, token: Option<&String>) -> Result<Response, MicropubError> {
+    let token = token.ok_or_else(|| MicropubError::invalid_request("missing 'token'"))?;
+    let now = chrono::Utc::now().format(DB_DATETIME_FORMAT).to_string();
+    let mut conn = db.dbconn()?;
+    // Unknown tokens are not an error, see https://www.rfc-editor.org/rfc/rfc7009#section-2.2
+    let revoked = diesel::update(
+        access_tokens::table
+            .filter(access_tokens::token_hash.eq(indieauth::token_hash(token)))
+            .filter(access_tokens::revoked_at.is_null()),
+    )
+    .set(access_tokens::revoked_at.eq(&now))
+    .execute(&mut conn)?;
+    info!("revoked {} access token(s)", revoked);
+
+    Ok(StatusCode::OK.into_response())
+}
+
+/// Deserializes form parameters into one of the request structs above.
+fn from_params<T: DeserializeOwned>(params: &HashMap<String, String>) -> Result<T, MicropubError> {
+    serde_json::to_value(params)
+        .and_then(serde_json::from_value)
+        .map_err(|e| MicropubError::invalid_request(e.to_string()))
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use chrono::Local;
 use diesel::prelude::*;
@@ -10,16 +11,17 @@ use urlencoding::decode;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::auth::TokenValidateResponse;
+use crate::auth::{metadata_link_from_header, metadata_link_from_html, IntrospectionResponse, TokenCache, TokenValidateResponse};
+use crate::config::TokenVerification;
 use crate::errors::*;
 use crate::handler::{MicropubDB, WithDB};
-use crate::models::{NewCategory, NewOriginalBlob, NewPost, NewPostHistory, NewPhoto, NewMediaUpload, Post};
-use crate::{media_util, post_util};
-use crate::schema::{categories, original_blobs, posts, photos, media};
+use crate::models::{Event, Location, NewCategory, NewEvent, NewLocation, NewOriginalBlob, NewPost, NewPostHistory, NewPostObject, NewPhoto, NewMediaUpload, NewReview, NewSyndication, Post, PostObject, Review};
+use crate::{indieauth, media_util, post_util};
+use crate::schema::{categories, events, locations, original_blobs, posts, photos, post_objects, media, reviews, syndications};
 
 use axum::{
     body::Body,
-    extract::{Multipart, Query},
+    extract::{FromRequest, Multipart, Query},
     response::{Response, IntoResponse},
 };
 use http::{header, StatusCode, HeaderValue};
@@ -28,6 +30,8 @@ use http::{header, StatusCode, HeaderValue};
 enum MicropubFormError {
     #[error("Required field '{0}' is missing.")]
     MissingField(String),
+    #[error("Field '{0}' has an invalid value.")]
+    InvalidField(String),
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -38,6 +42,7 @@ enum MicropubPropertyValue {
     Map(HashMap<String, MicropubPropertyValue>),
     VecMap(Vec<std::collections::HashMap<String, MicropubPropertyValue>>),
     ValueVec(Vec<MicropubPropertyValue>),
+    Number(f64),
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -63,17 +68,72 @@ struct Photo {
     alt: Option<String>,
 }
 
+/// Properties whose values are whole nested mf2 objects (e.g. an h-food for 'drank'). These are
+/// kept as the json the client sent rather than being mapped onto post columns.
+const NESTED_OBJECT_PROPERTIES: &[&str] = &["ate", "drank"];
+
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct NestedObject {
+    property: String,
+    value: serde_json::Value,
+}
+
+/// Properties that are read into the form's own fields. Anything else a client sends, other than
+/// mp-* commands, is kept as-is in the post's extra properties so that it isn't lost.
+const KNOWN_PROPERTIES: &[&str] = &[
+    "content", "name", "summary", "category", "published", "updated", "bookmark-of", "in-reply-to",
+    "like-of", "repost-of", "photo", "location", "checkin", "ate", "drank", "post-status", "visibility",
+];
+
+/// Properties that are only read into the form for one type of post. Other types keep them as
+/// extra properties, e.g. the url of an h-entry.
+const TYPE_PROPERTIES: &[(&str, &[&str])] = &[
+    ("event", &["start", "end", "url"]),
+    ("review", &["item", "rating", "best", "worst"]),
+];
+
+/// Values of `q` handled by handle_query, advertised in the `q=config` response.
+pub const SUPPORTED_QUERIES: &[&str] = &["config", "source", "syndicate-to", "category"];
+
+/// Allowed values of post-status. Drafts are only visible through q=source and previews.
+const POST_STATUSES: &[&str] = &["published", "draft"];
+
+/// Allowed values of visibility. Unlisted posts are left out of listings and feeds, private posts
+/// are only shown to authorized viewers.
+const VISIBILITIES: &[&str] = &["public", "unlisted", "private"];
+
+fn is_extra_property(h: &str, key: &str) -> bool {
+    let type_property = TYPE_PROPERTIES
+        .iter()
+        .any(|(t, properties)| *t == h && properties.contains(&key));
+    !KNOWN_PROPERTIES.contains(&key) && !type_property && !key.starts_with("mp-")
+}
+
+/// Parses a post's extra_properties column, which is None when there aren't any.
+fn parse_extra_properties(extra: Option<&str>) -> serde_json::Map<String, serde_json::Value> {
+    extra
+        .and_then(|e| {
+            serde_json::from_str(e)
+                .map_err(|err| error!("invalid extra properties {:?}: {:?}", e, err))
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+fn extra_properties_json(extra: &serde_json::Map<String, serde_json::Value>) -> Option<String> {
+    if extra.is_empty() {
+        None
+    } else {
+        Some(serde_json::Value::Object(extra.clone()).to_string())
+    }
+}
+
 // TODO:
 // - quill appears to include 'published' and 'created' properties
-// - food entries seem... complex. See food entry test case below
-//   e.g. a 'drank' property may have a whole sub type/properties object...
-//   I'd really like to support recording this for e.g. tea blogging but this might require a
-//   larger refactor.
 // - bookmark might have a bookmark-of property (possibly more likely to be a form encoded than
 //   json encoded entry
-// - review types (https://quill.p3k.io/review)
-//   quill doesn't appeart to include categories in the form but that would be nice to support
-//   adding a test case below, commented out
+// - quill doesn't appear to include categories in review forms (https://quill.p3k.io/review) but
+//   that would be nice to support
 #[derive(Debug, Deserialize)]
 struct MicropubFormBuilder {
     access_token: Option<String>,
@@ -82,11 +142,32 @@ struct MicropubFormBuilder {
     content_type: Option<String>,
     category: Option<Vec<String>>,
     name: Option<String>,
+    summary: Option<String>,
     created_at: Option<String>,
     updated_at: Option<String>,
     slug: Option<String>,
     bookmark_of: Option<String>,
+    in_reply_to: Option<String>,
+    like_of: Option<String>,
+    repost_of: Option<String>,
     photos: Option<Vec<Photo>>,
+    syndicate_to: Option<Vec<String>>,
+    start: Option<String>,
+    end: Option<String>,
+    location: Option<Place>,
+    checkin: Option<Place>,
+    url: Option<String>,
+    item: Option<ReviewItem>,
+    rating: Option<f64>,
+    best: Option<f64>,
+    worst: Option<f64>,
+    objects: Option<Vec<NestedObject>>,
+    extra_properties: Option<serde_json::Map<String, serde_json::Value>>,
+    post_status: Option<String>,
+    visibility: Option<String>,
+    /// A property given a value that can't be used, e.g. several values for a single valued
+    /// property. build() rejects the form with it.
+    invalid_field: Option<String>,
 }
 
 fn set_from_prop<F>(builder: &mut MicropubFormBuilder, setter: &mut F, props: &MicropubProperties, prop: &str) -> bool
@@ -96,6 +177,89 @@ where F: Fn(&mut MicropubFormBuilder, MicropubPropertyValue) {
     }).is_some()
 }
 
+/// The first string value of a property, whether given as a single value or a list.
+fn first_value(val: MicropubPropertyValue) -> Option<String> {
+    match val {
+        MicropubPropertyValue::Value(v) => Some(v),
+        MicropubPropertyValue::Values(mut vs) => vs.drain(..).next(),
+        _ => None,
+    }
+}
+
+/// Numeric properties such as rating may be numbers or numeric strings.
+fn number_value(val: MicropubPropertyValue) -> Option<f64> {
+    match val {
+        MicropubPropertyValue::Number(n) => Some(n),
+        MicropubPropertyValue::Value(v) => v.parse().ok(),
+        MicropubPropertyValue::Values(mut vs) => vs.drain(..).next().and_then(|v| v.parse().ok()),
+        MicropubPropertyValue::ValueVec(mut vs) => vs.drain(..).next().and_then(number_value),
+        _ => None,
+    }
+}
+
+/// Whether a review's rating is within its best and worst, which default to a 1 to 5 scale
+/// (http://microformats.org/wiki/h-review).
+fn rating_in_range(rating: f64, best: Option<f64>, worst: Option<f64>) -> bool {
+    (worst.unwrap_or(1.0)..=best.unwrap_or(5.0)).contains(&rating)
+}
+
+/// A reviewed item may be a plain name or url, or an embedded h-card/h-product/h-item.
+fn review_item(val: MicropubPropertyValue) -> Option<ReviewItem> {
+    match val {
+        MicropubPropertyValue::VecMap(mut objects) if !objects.is_empty() => {
+            match objects.swap_remove(0).remove("properties") {
+                Some(MicropubPropertyValue::Map(mut props)) => {
+                    let name = props.remove("name").and_then(first_value).filter(|n| !n.is_empty());
+                    let url = props.remove("url").and_then(first_value).filter(|u| !u.is_empty());
+                    Some(ReviewItem { name, url })
+                }
+                _ => None,
+            }
+        }
+        val => first_value(val).map(|v| {
+            if v.starts_with("http://") || v.starts_with("https://") {
+                ReviewItem { name: None, url: Some(v) }
+            } else {
+                ReviewItem { name: Some(v), url: None }
+            }
+        }),
+    }
+}
+
+/// Latitude and longitude of a geo: URI (RFC 5870), e.g. geo:37.786971,-122.399677;u=35
+fn parse_geo_uri(uri: &str) -> Option<(f64, f64)> {
+    let coords = uri.strip_prefix("geo:")?.split(';').next()?;
+    let mut parts = coords.split(',');
+    let latitude = parts.next()?.trim().parse().ok()?;
+    let longitude = parts.next()?.trim().parse().ok()?;
+    Some((latitude, longitude))
+}
+
+/// A location or checkin may be a geo: URI, a url, plain text, or an embedded h-card/h-adr/h-geo.
+fn place_from_value(val: MicropubPropertyValue) -> Option<Place> {
+    match val {
+        MicropubPropertyValue::VecMap(mut objects) if !objects.is_empty() => {
+            match objects.swap_remove(0).remove("properties") {
+                Some(MicropubPropertyValue::Map(mut props)) => {
+                    // an h-card may carry its coordinates in a nested geo property
+                    let geo = props.remove("geo").and_then(place_from_value).unwrap_or_default();
+                    Some(Place {
+                        name: ["name", "locality"]
+                            .iter()
+                            .find_map(|k| props.remove(*k).and_then(first_value))
+                            .or(geo.name),
+                        url: props.remove("url").and_then(first_value).or(geo.url),
+                        latitude: props.remove("latitude").and_then(number_value).or(geo.latitude),
+                        longitude: props.remove("longitude").and_then(number_value).or(geo.longitude),
+                    })
+                }
+                _ => None,
+            }
+        }
+        val => first_value(val).map(Place::from_text),
+    }
+}
+
 fn set_from_props<F>(builder: &mut MicropubFormBuilder, mut setter: F, props: &MicropubProperties, props_to_check: &[&str]) -> bool
 where F: Fn(&mut MicropubFormBuilder, MicropubPropertyValue) {
     for prop in props_to_check {
@@ -109,6 +273,36 @@ where F: Fn(&mut MicropubFormBuilder, MicropubPropertyValue) {
 
 type PropertySetter = Box<dyn Fn(&mut MicropubFormBuilder, MicropubPropertyValue)>;
 
+/// Setter for single valued properties such as in-reply-to or an event's start. The value may also
+/// be given as an embedded object (e.g. an h-cite), in which case its url is used.
+fn single_value_setter(prop: &'static str, set: fn(&mut MicropubFormBuilder, String)) -> PropertySetter {
+    Box::new(move |builder: &mut MicropubFormBuilder, val: MicropubPropertyValue| {
+        match val {
+            MicropubPropertyValue::Value(url) => set(builder, url),
+            MicropubPropertyValue::Values(mut urls) => {
+                if urls.len() == 1 {
+                    set(builder, urls.pop().expect("urls len was checked as 1"))
+                } else {
+                    error!("expected a single {} value, got {}", prop, urls.len());
+                    builder.invalid_field.get_or_insert(prop.into());
+                }
+            }
+            MicropubPropertyValue::VecMap(citations) => {
+                if let Some(citation) = citations.first()
+                    && let Some(MicropubPropertyValue::Map(citation_props)) = citation.get("properties")
+                    && let Some(MicropubPropertyValue::Values(urls)) = citation_props.get("url")
+                    && let Some(url) = urls.first()
+                {
+                    set(builder, url.clone())
+                } else {
+                    error!("unexpected {} object", prop)
+                }
+            }
+            _ => error!("unexpected {} property type", prop),
+        }
+    })
+}
+
 impl MicropubFormBuilder {
     fn new() -> Self {
         Self {
@@ -118,11 +312,30 @@ impl MicropubFormBuilder {
             content_type: None,
             category: None,
             name: None,
+            summary: None,
             created_at: None,
             updated_at: None,
             slug: None,
             bookmark_of: None,
+            in_reply_to: None,
+            like_of: None,
+            repost_of: None,
             photos: None,
+            syndicate_to: None,
+            start: None,
+            end: None,
+            location: None,
+            checkin: None,
+            url: None,
+            item: None,
+            rating: None,
+            best: None,
+            worst: None,
+            objects: None,
+            extra_properties: None,
+            post_status: None,
+            visibility: None,
+            invalid_field: None,
         }
     }
 
@@ -135,6 +348,24 @@ impl MicropubFormBuilder {
             builder.set_h(entry_type.strip_prefix("h-").unwrap_or(entry_type).into())
         }
 
+        // Nested objects are read from the raw json so that they're stored exactly as sent
+        let raw: serde_json::Value = serde_json::from_slice(json_bytes)?;
+        for property in NESTED_OBJECT_PROPERTIES {
+            let values = raw["properties"][property].as_array().into_iter().flatten();
+            for value in values.filter(|v| v.is_object()) {
+                builder.add_object(NestedObject {
+                    property: property.to_string(),
+                    value: value.clone(),
+                });
+            }
+        }
+        if let Some(properties) = raw["properties"].as_object() {
+            let h = builder.h.clone().unwrap_or_default();
+            for (key, values) in properties.iter().filter(|(k, _)| is_extra_property(&h, k)) {
+                builder.set_extra_property(key.clone(), values.clone());
+            }
+        }
+
         let prop_setter_pairs: Vec<(&[&str], PropertySetter)> = vec![
             (&["content", "content[html]"][..], Box::new(|builder: &mut MicropubFormBuilder, val: MicropubPropertyValue| {
                 match val {
@@ -174,6 +405,17 @@ impl MicropubFormBuilder {
                     _ => error!("unexpected name type")
                 };
             })),
+            (&["summary"][..], Box::new(|builder: &mut MicropubFormBuilder, val: MicropubPropertyValue| {
+                match val {
+                    MicropubPropertyValue::Values(vals) => {
+                        if let Some(s) = vals.first() {
+                            builder.set_summary((*s).clone())
+                        }
+                    }
+                    MicropubPropertyValue::Value(val) => builder.set_summary(val),
+                    _ => error!("unexpected summary type")
+                };
+            })),
             (&["category"][..], Box::new(|builder: &mut MicropubFormBuilder, props: MicropubPropertyValue| {
                 match props {
                     MicropubPropertyValue::Value(c) => {
@@ -227,9 +469,67 @@ impl MicropubFormBuilder {
                     _ => eprintln!("unexpected bookmark_of property type"),
                 }
             })),
+            (&["in-reply-to"][..], single_value_setter("in-reply-to", MicropubFormBuilder::set_in_reply_to)),
+            (&["like-of"][..], single_value_setter("like-of", MicropubFormBuilder::set_like_of)),
+            (&["repost-of"][..], single_value_setter("repost-of", MicropubFormBuilder::set_repost_of)),
+            (&["start"][..], single_value_setter("start", MicropubFormBuilder::set_start)),
+            (&["end"][..], single_value_setter("end", MicropubFormBuilder::set_end)),
+            (&["url"][..], single_value_setter("url", MicropubFormBuilder::set_url)),
+            (&["location"][..], Box::new(|builder: &mut MicropubFormBuilder, props: MicropubPropertyValue| {
+                match place_from_value(props) {
+                    Some(l) => builder.location = Some(l),
+                    None => error!("unexpected location type"),
+                }
+            })),
+            (&["checkin"][..], Box::new(|builder: &mut MicropubFormBuilder, props: MicropubPropertyValue| {
+                match place_from_value(props) {
+                    Some(c) => builder.checkin = Some(c),
+                    None => error!("unexpected checkin type"),
+                }
+            })),
+            (&["item"][..], Box::new(|builder: &mut MicropubFormBuilder, props: MicropubPropertyValue| {
+                match review_item(props) {
+                    Some(item) => builder.item = Some(item),
+                    None => error!("unexpected item type"),
+                }
+            })),
+            (&["rating"][..], Box::new(|builder: &mut MicropubFormBuilder, props: MicropubPropertyValue| {
+                builder.rating = number_value(props);
+            })),
+            (&["best"][..], Box::new(|builder: &mut MicropubFormBuilder, props: MicropubPropertyValue| {
+                builder.best = number_value(props);
+            })),
+            (&["worst"][..], Box::new(|builder: &mut MicropubFormBuilder, props: MicropubPropertyValue| {
+                builder.worst = number_value(props);
+            })),
             (&["photo"][..], Box::new(|builder: &mut MicropubFormBuilder, props: MicropubPropertyValue| {
                 builder.on_photo_props(props);
             })),
+            (&["post-status"][..], Box::new(|builder: &mut MicropubFormBuilder, props: MicropubPropertyValue| {
+                match first_value(props) {
+                    Some(status) => builder.set_post_status(status),
+                    None => error!("unexpected post-status type"),
+                }
+            })),
+            (&["visibility"][..], Box::new(|builder: &mut MicropubFormBuilder, props: MicropubPropertyValue| {
+                match first_value(props) {
+                    Some(visibility) => builder.set_visibility(visibility),
+                    None => error!("unexpected visibility type"),
+                }
+            })),
+            (&["mp-syndicate-to"][..], Box::new(|builder: &mut MicropubFormBuilder, props: MicropubPropertyValue| {
+                match props {
+                    MicropubPropertyValue::Value(target) => {
+                        builder.add_syndicate_to(target);
+                    }
+                    MicropubPropertyValue::Values(targets) => {
+                        for t in targets {
+                            builder.add_syndicate_to(t);
+                        }
+                    }
+                    _ => error!("unexpected mp-syndicate-to type")
+                };
+            })),
         ];
 
         for (props, setter) in prop_setter_pairs {
@@ -240,18 +540,82 @@ impl MicropubFormBuilder {
     }
 
     fn build(self) -> Result<MicropubForm, MicropubFormError> {
+        if let Some(field) = self.invalid_field {
+            return Err(MicropubFormError::InvalidField(field));
+        }
+        let h = self.h.ok_or(MicropubFormError::MissingField("h".into()))?;
+        // an event's location is kept with the event rather than as the post's location
+        let (event, location) = if h == "event" {
+            let event = EventProperties {
+                start: self.start.ok_or(MicropubFormError::MissingField("start".into()))?,
+                end: self.end,
+                location: self.location.and_then(Place::label),
+                url: self.url,
+            };
+            (Some(event), None)
+        } else {
+            (None, self.location)
+        };
+        let review = if h == "review" {
+            let item = self.item.ok_or(MicropubFormError::MissingField("item".into()))?;
+            if let Some(rating) = self.rating
+                && !rating_in_range(rating, self.best, self.worst)
+            {
+                return Err(MicropubFormError::InvalidField("rating".into()));
+            }
+            Some(ReviewProperties {
+                item,
+                rating: self.rating,
+                best: self.best,
+                worst: self.worst,
+            })
+        } else {
+            None
+        };
+        if let Some(status) = &self.post_status
+            && !POST_STATUSES.contains(&status.as_str())
+        {
+            return Err(MicropubFormError::InvalidField("post-status".into()));
+        }
+        if let Some(visibility) = &self.visibility
+            && !VISIBILITIES.contains(&visibility.as_str())
+        {
+            return Err(MicropubFormError::InvalidField("visibility".into()));
+        }
+        let objects = self.objects.unwrap_or_default();
+        // events are commonly created with only a name and start, reviews may only have a rating
+        // and ate/drank entries often only have a summary
+        let content = match self.content {
+            Some(c) => c,
+            None if event.is_some() || review.is_some() || !objects.is_empty() => String::new(),
+            None => return Err(MicropubFormError::MissingField("content".into())),
+        };
+
         Ok(MicropubForm {
             access_token: self.access_token,
-            h: self.h.ok_or(MicropubFormError::MissingField("h".into()))?,
-            content: self.content.ok_or(MicropubFormError::MissingField("content".into()))?,
+            h,
+            content,
             content_type: self.content_type,
             category: self.category.unwrap_or_default(),
             name: self.name,
+            summary: self.summary,
             created_at: self.created_at,
             updated_at: self.updated_at,
             slug: self.slug,
             bookmark_of: self.bookmark_of,
+            in_reply_to: self.in_reply_to,
+            like_of: self.like_of,
+            repost_of: self.repost_of,
             photos: self.photos,
+            syndicate_to: self.syndicate_to.unwrap_or_default(),
+            event,
+            review,
+            objects,
+            checkin: self.checkin,
+            location,
+            extra_properties: self.extra_properties.unwrap_or_default(),
+            post_status: self.post_status,
+            visibility: self.visibility,
         })
     }
 
@@ -279,6 +643,10 @@ impl MicropubFormBuilder {
         self.name = Some(val);
     }
 
+    fn set_summary(&mut self, val: String) {
+        self.summary = Some(val);
+    }
+
     fn set_created_at(&mut self, val: String) {
         self.created_at = Some(val)
     }
@@ -291,10 +659,61 @@ impl MicropubFormBuilder {
         self.bookmark_of = Some(val)
     }
 
+    fn set_in_reply_to(&mut self, val: String) {
+        self.in_reply_to = Some(val)
+    }
+
+    fn set_like_of(&mut self, val: String) {
+        self.like_of = Some(val)
+    }
+
+    fn set_repost_of(&mut self, val: String) {
+        self.repost_of = Some(val)
+    }
+
+    fn set_post_status(&mut self, val: String) {
+        self.post_status = Some(val)
+    }
+
+    fn set_visibility(&mut self, val: String) {
+        self.visibility = Some(val)
+    }
+
+    fn set_start(&mut self, val: String) {
+        self.start = Some(val)
+    }
+
+    fn set_end(&mut self, val: String) {
+        self.end = Some(val)
+    }
+
+    fn set_url(&mut self, val: String) {
+        self.url = Some(val)
+    }
+
+    fn add_syndicate_to(&mut self, val: String) {
+        self.syndicate_to.get_or_insert_with(Vec::new).push(val);
+    }
+
+    fn add_object(&mut self, val: NestedObject) {
+        self.objects.get_or_insert_with(Vec::new).push(val);
+    }
+
+    fn set_extra_property(&mut self, key: String, val: serde_json::Value) {
+        self.extra_properties.get_or_insert_with(serde_json::Map::new).insert(key, val);
+    }
+
     fn add_photo(&mut self, val: Photo) {
         self.photos.get_or_insert_with(Vec::new).push(val);
     }
 
+    fn set_last_photo_alt(&mut self, val: String) {
+        match self.photos.as_mut().and_then(|photos| photos.last_mut()) {
+            Some(photo) => photo.alt = Some(val),
+            None => warn!("ignoring photo alt text without a preceding photo"),
+        }
+    }
+
     fn on_photo_props(
         &mut self,
         props: MicropubPropertyValue) 
@@ -335,11 +754,118 @@ impl MicropubFormBuilder {
                     self.on_photo_props(photo)
                 }
             }
+            MicropubPropertyValue::Number(_) => error!("unexpected photo type"),
+        }
+    }
+}
+
+/// A checkin venue or location
+#[derive(Clone, Default, PartialEq, Debug, Deserialize)]
+struct Place {
+    name: Option<String>,
+    url: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+}
+
+impl Place {
+    fn from_text(text: String) -> Self {
+        if let Some((latitude, longitude)) = parse_geo_uri(&text) {
+            Place { latitude: Some(latitude), longitude: Some(longitude), ..Default::default() }
+        } else if text.starts_with("http://") || text.starts_with("https://") {
+            Place { url: Some(text), ..Default::default() }
+        } else {
+            Place { name: Some(text), ..Default::default() }
+        }
+    }
+
+    fn from_location(l: &Location) -> Self {
+        Place {
+            name: l.name.clone(),
+            url: l.url.clone(),
+            latitude: l.latitude,
+            longitude: l.longitude,
+        }
+    }
+
+    /// A short description of the place, e.g. for an event's location.
+    fn label(self) -> Option<String> {
+        let coords = self.geo_uri();
+        self.name.or(self.url).or(coords)
+    }
+
+    fn geo_uri(&self) -> Option<String> {
+        self.latitude
+            .zip(self.longitude)
+            .map(|(lat, long)| format!("geo:{},{}", lat, long))
+    }
+
+    fn to_mf2(&self, h: &str) -> serde_json::Value {
+        let mut props = serde_json::Map::new();
+        if let Some(name) = &self.name {
+            props.insert("name".into(), json!([name]));
+        }
+        if let Some(url) = &self.url {
+            props.insert("url".into(), json!([url]));
+        }
+        if let Some(lat) = self.latitude {
+            props.insert("latitude".into(), json!([lat]));
+        }
+        if let Some(long) = self.longitude {
+            props.insert("longitude".into(), json!([long]));
+        }
+        json!({"type": [h], "properties": props})
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct EventProperties {
+    start: String,
+    end: Option<String>,
+    location: Option<String>,
+    url: Option<String>,
+}
+
+impl EventProperties {
+    fn from_event(e: &Event) -> Self {
+        Self {
+            start: e.starts_at.clone(),
+            end: e.ends_at.clone(),
+            location: e.location.clone(),
+            url: e.url.clone(),
         }
     }
 }
 
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+struct ReviewItem {
+    name: Option<String>,
+    url: Option<String>,
+}
+
 #[derive(Debug, PartialEq, Clone)]
+struct ReviewProperties {
+    item: ReviewItem,
+    rating: Option<f64>,
+    best: Option<f64>,
+    worst: Option<f64>,
+}
+
+impl ReviewProperties {
+    fn from_review(r: &Review) -> Self {
+        Self {
+            item: ReviewItem {
+                name: r.item_name.clone(),
+                url: r.item_url.clone(),
+            },
+            rating: r.rating,
+            best: r.best,
+            worst: r.worst,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
 struct MicropubForm {
     /// Access token (token used to authenticate the operation).
     /// May be used in place of a bearer token authorization header.
@@ -363,6 +889,9 @@ struct MicropubForm {
     /// Note that h-notes do not contain a name.
     name: Option<String>,
 
+    /// Short plain text summary of the entry
+    summary: Option<String>,
+
     /// Created and Updated at datetimes of the post
     /// The database schema has a default of the current time but this can also be provided at post
     /// time.
@@ -375,10 +904,38 @@ struct MicropubForm {
     /// Indicates entry is a bookmark type. String should be a URL.
     bookmark_of: Option<String>,
 
+    /// URLs that the entry is a reply to, a like of or a repost of.
+    in_reply_to: Option<String>,
+    like_of: Option<String>,
+    repost_of: Option<String>,
+
     /// Photos included with the entry
     photos: Option<Vec<Photo>>,
 
-    // TODO: support additional fields and properties
+    /// uids of the syndication targets the client asked us to syndicate the entry to
+    syndicate_to: Vec<String>,
+
+    /// Present for h-event entries
+    event: Option<EventProperties>,
+
+    /// Present for h-review entries
+    review: Option<ReviewProperties>,
+
+    /// Nested mf2 objects, e.g. from ate or drank
+    objects: Vec<NestedObject>,
+
+    /// Venue checked in to and where the entry was posted from
+    checkin: Option<Place>,
+    location: Option<Place>,
+
+    /// Any other properties the client sent, as mf2 json values
+    extra_properties: serde_json::Map<String, serde_json::Value>,
+
+    /// "published" (the default) or "draft"
+    post_status: Option<String>,
+
+    /// "public" (the default), "unlisted" or "private"
+    visibility: Option<String>,
 }
 
 impl MicropubForm {
@@ -397,7 +954,26 @@ impl MicropubForm {
                 },
                 "category" | "category[]" => builder.add_category(v.into_owned()),
                 "name" => builder.set_name(v.into_owned()),
+                "summary" => builder.set_summary(v.into_owned()),
+                "published" => builder.set_created_at(v.into_owned()),
+                "mp-slug" => builder.set_slug(v.into_owned()),
                 "bookmark-of" => builder.set_bookmark_of(v.into_owned()),
+                "in-reply-to" => builder.set_in_reply_to(v.into_owned()),
+                "like-of" => builder.set_like_of(v.into_owned()),
+                "repost-of" => builder.set_repost_of(v.into_owned()),
+                "post-status" => builder.set_post_status(v.into_owned()),
+                "visibility" => builder.set_visibility(v.into_owned()),
+                "start" => builder.set_start(v.into_owned()),
+                "end" => builder.set_end(v.into_owned()),
+                "location" => builder.location = Some(Place::from_text(v.into_owned())),
+                "checkin" => builder.checkin = Some(Place::from_text(v.into_owned())),
+                "url" => builder.set_url(v.into_owned()),
+                "photo" | "photo[]" | "photo[value]" | "photo[][value]" => {
+                    builder.add_photo(Photo { url: v.into_owned(), alt: None })
+                },
+                // alt text applies to the photo given just before it
+                "photo[alt]" | "photo[][alt]" => builder.set_last_photo_alt(v.into_owned()),
+                "mp-syndicate-to" | "mp-syndicate-to[]" => builder.add_syndicate_to(v.into_owned()),
                 _ => (),
             }
         }
@@ -423,15 +999,30 @@ impl MicropubForm {
             content_type: p.content_type.clone(),
             category: Vec::from(categories),
             name: p.name.clone(),
+            summary: p.summary.clone(),
             created_at: Some(p.created_at.clone()),
             updated_at: Some(p.updated_at.clone()),
             slug: Some(p.slug.clone()),
             bookmark_of: p.bookmark_of.clone(),
+            in_reply_to: p.in_reply_to.clone(),
+            like_of: p.like_of.clone(),
+            repost_of: p.repost_of.clone(),
             photos: photos_out,
+            syndicate_to: vec![],
+            event: None,
+            review: None,
+            objects: vec![],
+            checkin: None,
+            location: None,
+            extra_properties: parse_extra_properties(p.extra_properties.as_deref()),
+            post_status: Some(p.post_status.clone()),
+            visibility: Some(p.visibility.clone()),
         }
     }
 
-    fn to_properties_json(&self) -> Result<String, anyhow::Error> {
+    /// The entry as mf2 json. If `properties` is not empty only those properties are included and,
+    /// as the spec describes for q=source with properties[], the type is left out.
+    fn to_properties(&self, properties: &[String]) -> serde_json::Value {
         let mut result = json!({
             "type": vec![format!("h-{}", self.h)],
             "properties": {
@@ -462,9 +1053,71 @@ impl MicropubForm {
         if let Some(n) = &self.name {
             m.insert("name".into(), json!(vec![n]));
         }
+        if let Some(summary) = &self.summary {
+            m.insert("summary".into(), json!(vec![summary]));
+        }
+        if let Some(status) = &self.post_status {
+            m.insert("post-status".into(), json!(vec![status]));
+        }
+        if let Some(visibility) = &self.visibility {
+            m.insert("visibility".into(), json!(vec![visibility]));
+        }
         if let Some(b) = &self.bookmark_of {
             m.insert("bookmark-of".into(), json!(vec![b]));
         }
+        if let Some(r) = &self.in_reply_to {
+            m.insert("in-reply-to".into(), json!(vec![r]));
+        }
+        if let Some(l) = &self.like_of {
+            m.insert("like-of".into(), json!(vec![l]));
+        }
+        if let Some(r) = &self.repost_of {
+            m.insert("repost-of".into(), json!(vec![r]));
+        }
+        if let Some(event) = &self.event {
+            m.insert("start".into(), json!(vec![&event.start]));
+            if let Some(end) = &event.end {
+                m.insert("end".into(), json!(vec![end]));
+            }
+            if let Some(location) = &event.location {
+                m.insert("location".into(), json!(vec![location]));
+            }
+            if let Some(url) = &event.url {
+                m.insert("url".into(), json!(vec![url]));
+            }
+        }
+        if let Some(review) = &self.review {
+            let mut item_props = serde_json::Map::new();
+            if let Some(name) = &review.item.name {
+                item_props.insert("name".into(), json!(vec![name]));
+            }
+            if let Some(url) = &review.item.url {
+                item_props.insert("url".into(), json!(vec![url]));
+            }
+            m.insert("item".into(), json!([{"type": ["h-product"], "properties": item_props}]));
+            for (key, val) in [("rating", review.rating), ("best", review.best), ("worst", review.worst)] {
+                if let Some(v) = val {
+                    m.insert(key.into(), json!(vec![v]));
+                }
+            }
+        }
+        if let Some(checkin) = &self.checkin {
+            m.insert("checkin".into(), json!([checkin.to_mf2("h-card")]));
+        }
+        if let Some(location) = &self.location {
+            // coordinates alone round trip as a geo: URI
+            let value = match location.geo_uri() {
+                Some(uri) if location.name.is_none() && location.url.is_none() => json!(uri),
+                _ => location.to_mf2("h-adr"),
+            };
+            m.insert("location".into(), json!([value]));
+        }
+        for object in &self.objects {
+            let values = m.entry(object.property.clone()).or_insert_with(|| json!([]));
+            if let Some(values) = values.as_array_mut() {
+                values.push(object.value.clone());
+            }
+        }
         if let Some(photos) = &self.photos {
             let photos_out: Vec<serde_json::Value> = photos.iter().map(|p| {
                 let mut photo = json!({"value": p.url});
@@ -475,8 +1128,21 @@ impl MicropubForm {
             }).collect();
             m.insert("photo".into(), json!(photos_out));
         }
+        for (key, values) in &self.extra_properties {
+            m.entry(key.clone()).or_insert_with(|| values.clone());
+        }
+
+        if properties.is_empty() {
+            return result;
+        }
+        m.retain(|k, _v| properties.contains(k));
+        json!({
+            "properties": m,
+        })
+    }
 
-        Ok(serde_json::to_string(&result)?)
+    fn to_properties_json(&self, properties: &[String]) -> Result<String, anyhow::Error> {
+        Ok(serde_json::to_string(&self.to_properties(properties))?)
     }
 
 }
@@ -490,310 +1156,843 @@ fn get_latest_post_id(conn: &mut SqliteConnection) -> Result<i32, diesel::result
 pub async fn handle_post(
     http_client: reqwest::Client,
     db: Arc<MicropubDB>,
+    token_cache: Arc<TokenCache>,
     site_config: Arc<crate::MicropubSiteConfig>,
-    headers: http::header::HeaderMap,
-    body: axum::body::Body,
-) -> Result<impl IntoResponse, StatusCode> {
+    request: axum::extract::Request,
+) -> Result<impl IntoResponse, MicropubError> {
+    let headers = request.headers().clone();
     let content_type = headers.get("Content-Type");
-    let auth = headers.get("Authorization");
-    info!("micropub post headers: {:?}", headers);
-
-    if auth.is_none() {
-        return Err(StatusCode::FORBIDDEN);
+    let mut logged_headers = headers.clone();
+    if logged_headers.contains_key(header::AUTHORIZATION) {
+        logged_headers.insert(header::AUTHORIZATION, HeaderValue::from_static("[redacted]"));
     }
-
-    let auth_header_val: String = auth
-        .expect("checked auth contents")
-        .to_str()
-        .map_err(|e| {
-            error!("error getting authorization header ascii contents: {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-
-        })?
-        .into();
-
-    let validate_response = verify_auth(
-        http_client,
-        site_config.clone(),
-        &auth_header_val
-    ).await?;
-
-    if validate_response.me != site_config.micropub.host_website {
-        error!(
-            "mismatched authorization: me: {} host_website: {}",
-           validate_response.me,
-           site_config.micropub.host_website
-        );
-        return Err(StatusCode::FORBIDDEN);
+    info!("micropub post headers: {:?}", logged_headers);
+
+    let is_json = content_type
+        .and_then(|ct| ct.to_str().ok())
+        .map(|ct| ct.to_lowercase().contains("application/json"))
+        .unwrap_or(false);
+    let is_multipart = content_type
+        .and_then(|ct| ct.to_str().ok())
+        .map(|ct| ct.to_lowercase().starts_with("multipart/form-data"))
+        .unwrap_or(false);
+
+    // With an Authorization header the token is verified before the body is read. Otherwise it
+    // has to be an access_token field of a form encoded or multipart body, and only as much of the
+    // body as it takes to find it is read before it's verified. The token is taken out of the
+    // fields so that it isn't stored with the post.
+    let (validate_response, body_bytes, multipart) = if headers.contains_key(header::AUTHORIZATION) {
+        let validate_response = authorize(
+            http_client.clone(),
+            site_config.clone(),
+            &db,
+            &token_cache,
+            &headers,
+            None,
+        ).await?;
+        if is_multipart {
+            let mut multipart = multipart_from_request(request).await?;
+            let (mut fields, mut files) = (vec![], vec![]);
+            read_multipart(&mut multipart, &mut fields, &mut files).await?;
+            (validate_response, bytes::Bytes::new(), Some((fields, files)))
+        } else {
+            let body_bytes = read_body(request, site_config.micropub.media_endpoint_max_upload_length).await?;
+            if !is_json && parse(&body_bytes).any(|(k, _)| k == "access_token") {
+                return Err(access_token_sent_twice());
+            }
+            (validate_response, body_bytes, None)
+        }
+    } else if is_multipart {
+        let mut multipart = multipart_from_request(request).await?;
+        let mut fields = vec![];
+        let body_token = read_multipart_access_token(&mut multipart, &mut fields).await?;
+        let validate_response = authorize(
+            http_client.clone(),
+            site_config.clone(),
+            &db,
+            &token_cache,
+            &headers,
+            Some(&body_token),
+        ).await?;
+        let mut files = vec![];
+        read_multipart(&mut multipart, &mut fields, &mut files).await?;
+        (validate_response, bytes::Bytes::new(), Some((fields, files)))
+    } else if is_json {
+        warn!("unauthorized micropub request - missing access token");
+        return Err(MicropubError::unauthorized("missing access token"));
+    } else {
+        let body_bytes = read_body(request, MAX_UNAUTHORIZED_FORM_LENGTH).await?;
+        let mut fields: Vec<(String, String)> = parse(&body_bytes).into_owned().collect();
+        let body_token = take_access_token(&mut fields);
+        let validate_response = authorize(
+            http_client.clone(),
+            site_config.clone(),
+            &db,
+            &token_cache,
+            &headers,
+            body_token.as_deref(),
+        ).await?;
+        (validate_response, bytes::Bytes::from(encode_form(&fields)), None)
+    };
+    match &multipart {
+        Some((fields, _files)) => info!("micropub post multipart fields: {:?}", fields),
+        None => info!("micropub post body: {:?}", body_bytes),
     }
 
-    let body_bytes: bytes::Bytes = axum::body::to_bytes(body, site_config.micropub.media_endpoint_max_upload_length)
-        .await
-        .map_err(|e| {
-            error!("error reading bytes from body: {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-    info!("micropub post body: {:?}", body_bytes);
     // if content type is json, attempt to decode and see whether this is an action (update/delete)
     // or if it's a create.
-    if let Some(ct) = content_type
-        && let Ok(content_type_str) = ct.to_str()
-        && content_type_str.to_lowercase().contains("application/json") {
+    if is_json {
         let body_byte_slice: &[u8] = &body_bytes[..];
         let json_parse_result: serde_json::Result<serde_json::Value> = serde_json::from_slice(body_byte_slice);
         match json_parse_result {
             Ok(json) => {
                 info!("micropub post body parsed json: {:?}", json);
-                if let Some(obj) = json.as_object() {
-                    match obj.get("action") {
-                        Some(serde_json::Value::String(action)) => {
-                            if action == "update" {
-                                return handle_update(db, site_config, obj).await;
-                            }
-                        },
-                        Some(_v) => {
-                        },
-                        None => {
-                        },
+                if let Some(obj) = json.as_object()
+                    && let Some(serde_json::Value::String(action)) = obj.get("action")
+                {
+                    if let Some(scope) = scope_for_action(action) {
+                        require_scope(&validate_response, scope)?;
                     }
+                    let url = obj.get("url").and_then(|v| v.as_str());
+                    return match action.as_str() {
+                        "update" => handle_update(db, site_config, obj).await,
+                        "delete" => handle_delete(db, site_config, url, true).await,
+                        "undelete" => handle_delete(db, site_config, url, false).await,
+                        a => {
+                            warn!("unsupported micropub action: {:?}", a);
+                            Err(MicropubError::invalid_request(format!("unsupported action '{}'", a)))
+                        }
+                    };
                 }
             },
             Err(e) => {
                 warn!("failed to parse json despite content type being application/json, letting request fall though to create_post: {:?}", e);
             },
         }
+    } else if !is_multipart && let Some((action, url)) = action_from_form_bytes(&body_bytes) {
+        if let Some(scope) = scope_for_action(&action) {
+            require_scope(&validate_response, scope)?;
+        }
+        // form encoded requests may only carry delete and undelete actions, updates must be json
+        return match action.as_str() {
+            "delete" => handle_delete(db, site_config, url.as_deref(), true).await,
+            "undelete" => handle_delete(db, site_config, url.as_deref(), false).await,
+            a => {
+                warn!("unsupported form encoded micropub action: {:?}", a);
+                Err(MicropubError::invalid_request(format!("unsupported form encoded action '{}'", a)))
+            }
+        };
     }
 
-    let slug = create_post(
-        db.clone(),
-        content_type,
-        body_bytes,
-        validate_response.client_id.as_str()
-    ).await?;
+    require_scope(&validate_response, "create")?;
+
+    let slug = if let Some((fields, files)) = multipart {
+        create_multipart_post(
+            http_client,
+            db.clone(),
+            site_config.clone(),
+            fields,
+            files,
+            validate_response.client_id.as_str()
+        ).await?
+    } else {
+        create_post(
+            db.clone(),
+            content_type,
+            body_bytes,
+            validate_response.client_id.as_str()
+        ).await?
+    };
 
     Response::builder()
         .status(StatusCode::CREATED)
-        .header(header::LOCATION, format!("https://davidwilemski.com/{}", slug))
+        .header(header::LOCATION, site_config.micropub.site_url(&slug))
         .body(Body::empty())
         .map_err(|e| {
             error!("error building response {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            MicropubError::internal("error building response")
         })
 }
 
+/// Most bytes of a form encoded body, or of the multipart fields before its access_token, read
+/// from a request without an Authorization header before its token is verified.
+const MAX_UNAUTHORIZED_FORM_LENGTH: usize = 256 * 1024;
+
+const DEFAULT_SOURCE_LIST_LIMIT: i64 = 10;
+const MAX_SOURCE_LIST_LIMIT: i64 = 100;
+
 async fn handle_update(
     db: Arc<MicropubDB>,
     site_config: Arc<crate::MicropubSiteConfig>,
     json: &serde_json::Map<String, serde_json::Value>,
-) -> Result<Response<Body>, StatusCode> {
+) -> Result<Response<Body>, MicropubError> {
     info!("handling update!!! json: {:?}", json);
 
     let url = json.get("url").and_then(|v| v.as_str())
         .ok_or_else(|| {
             error!("request json did not contain 'url' key: {:?}", json);
-            StatusCode::BAD_REQUEST
-        })?;
-    let slug = url.strip_prefix(site_config.micropub.host_website.as_str())
-        .ok_or(StatusCode::BAD_REQUEST)
-        .inspect_err(|_e| {
-            error!("provided url {:?} did not contain host website prefix {:?}", url, site_config.micropub.host_website);
+            MicropubError::invalid_request("update request is missing 'url'")
         })?;
+    let slug = slug_from_url(&site_config, url)?;
 
-    // get post
+    // All operations, along with the history row, are applied in a single transaction so that an
+    // invalid operation part way through doesn't leave the post partially updated.
     let mut conn = db.dbconn()?;
+    conn.transaction::<_, MicropubError, _>(|conn| {
+        let mut post: Post = Post::by_slug(slug)
+            .first::<Post>(conn)?;
+        let original_post = post.clone();
+
+        // handle the update operations:
+        // "The values of each property inside the replace, add or delete keys MUST be an array, even if there is only a single value."
+
+        if let Some(replacements) = json.get("replace") {
+            let replacements = replacements.as_object()
+                .ok_or_else(|| MicropubError::invalid_request("'replace' must be an object"))?;
+            for (key, vs) in replacements {
+                replace_property(conn, &mut post, key, property_values(key, vs)?)?;
+            }
+        }
 
-    let mut post: Post = Post::by_slug(slug)
-        .first::<Post>(&mut conn)
-        .map_err(|e| db.handle_errors(e))?;
-    let original_post = post.clone();
-
-    // handle the update operations:
-    // "The values of each property inside the replace, add or delete keys MUST be an array, even if there is only a single value."
-
-    // handle replacements
-    if let Some(replacements) = json.get("replace").and_then(|r| r.as_object()) {
-        let mut results = replacements.into_iter().map(|(key, vs)| {
-            if let Some(values) = vs.as_array() {
-                // TODO handle other properties here
-                match key.as_str() {
-                    // TODO content[html]
-                    "content" => {
-                        match values.first() {
-                            Some(serde_json::Value::String(c)) => {
-                                info!("updating content of post with slug {:?}", post.slug);
-                                post.content_type = None;
-                                post.content = Some(c.into());
-                            },
-                            Some(serde_json::Value::Object(c)) => {
-                                // if c is an object with "html" as a key, change content type to
-                                // html and also the content value
-                                c.get("html").and_then(|v| v.as_str()).into_iter().for_each(|html_content| {
-                                    eprintln!("new content: {:?}", html_content);
-                                    info!("updating html content of post with slug {:?}", post.slug);
-                                    post.content = Some(html_content.into());
-                                    post.content_type = Some("html".into());
-                                    eprintln!("post: {:?}", post);
-                                });
-                            }
-                            Some(_) => {
-                                warn!("unhandled content structure: {:?}", values);
-                            },
-                            None => (),
-                        }
-                        Ok(())
-                    },
-                    "name" => {
-                        post.name = values.first().and_then(|c| c.as_str()).map(|c| c.into());
-                        Ok(())
-                    },
-                    "category" => {
-                        db.run_txn(|conn| {
-                            use crate::schema::categories::dsl::*;
-                            diesel::delete(
-                                categories
-                                    .filter(post_id.eq(post.id))
-                            ).execute(conn)?;
-
-                            let maybe_categories = vs.as_array()
-                                .map(|v| {
-                                    v.iter()
-                                        .flat_map(|v| v.as_str().ok_or(StatusCode::BAD_REQUEST)
-                                                  .map(|c| NewCategory { post_id: post.id, category: c }))
-                                        .collect::<Vec<NewCategory>>()
-                                });
-                            if let Some(new_categories) = maybe_categories {
-                                diesel::insert_into(categories)
-                                    .values(&new_categories)
-                                    .execute(conn)?;
-                            }
-                            Ok(())
-                        }).map_err(|e| e.into())
-                    },
-                    k => {
-                        warn!("unhandled key for replace action: {:?}", k);
-                        Ok(())
-                    },
+        if let Some(additions) = json.get("add") {
+            let additions = additions.as_object()
+                .ok_or_else(|| MicropubError::invalid_request("'add' must be an object"))?;
+            for (key, vs) in additions {
+                add_property(conn, &mut post, key, property_values(key, vs)?)?;
+            }
+        }
+
+        if let Some(deletes) = json.get("delete") {
+            if let Some(delete_as_array) = deletes.as_array() {
+                // deleting entire properties
+                for key in delete_as_array {
+                    let key = key.as_str()
+                        .ok_or_else(|| MicropubError::invalid_request("'delete' property names must be strings"))?;
+                    delete_property(conn, &mut post, key)?;
+                }
+            } else if let Some(delete_as_obj) = deletes.as_object() {
+                // deleting individual values of properties
+                for (key, vs) in delete_as_obj {
+                    delete_property_values(conn, &mut post, key, property_values(key, vs)?)?;
                 }
             } else {
-                error!("replace: values for {:?} were not an array. {}", key, vs);
-                Err(StatusCode::BAD_REQUEST)
+                return Err(MicropubError::invalid_request("'delete' must be an array or object"));
             }
-        });
-        results.try_fold((), |_acc, r: Result<(), StatusCode>| r)?;
-    }
-
-    // handle additions
-    if let Some(additions) = json.get("add").and_then(|r| r.as_object()) {
-        let mut results = additions.into_iter().map(|(key, vs)| {
-            if vs.as_array().is_some() {
-                // TODO handle other properties here
-                match key.as_str() {
-                    "category" => {
-                        db.run_txn(|conn| {
-                            use crate::schema::categories::dsl::*;
-                            let maybe_categories = vs.as_array()
-                                .map(|v| {
-                                    v.iter()
-                                        .flat_map(|v| v.as_str().ok_or(StatusCode::BAD_REQUEST)
-                                                  .map(|c| NewCategory { post_id: post.id, category: c }))
-                                        .collect::<Vec<NewCategory>>()
-                                });
-                            if let Some(new_categories) = maybe_categories {
-                                diesel::insert_into(categories)
-                                    .values(&new_categories)
-                                    .execute(conn)?;
-                            }
-                            Ok(())
-                        }).map_err(|e| e.into())
-                    },
-                    k => {
-                        warn!("unhandled key for add action: {:?}", k);
-                        Ok(())
-                    },
-                }
-            } else {
-                error!("replace: values for {:?} were not an array. {}", key, vs);
-                Err(StatusCode::BAD_REQUEST)
+        }
+
+        let new_updated_at = Local::now().with_timezone(&site_config.micropub.current_timezone_offset)
+            .format("%Y-%m-%d %H:%M:%S");
+
+        use crate::schema::posts::dsl as posts_dsl;
+        let rows_updated = diesel::update(
+            posts_dsl::posts
+                .filter(posts_dsl::id.eq(post.id))
+        ).set(
+            (
+                posts_dsl::slug.eq(&post.slug),
+                posts_dsl::name.eq(&post.name),
+                posts_dsl::content.eq(&post.content),
+                posts_dsl::content_type.eq(&post.content_type),
+                posts_dsl::summary.eq(&post.summary),
+                posts_dsl::bookmark_of.eq(&post.bookmark_of),
+                posts_dsl::in_reply_to.eq(&post.in_reply_to),
+                posts_dsl::like_of.eq(&post.like_of),
+                posts_dsl::repost_of.eq(&post.repost_of),
+                posts_dsl::extra_properties.eq(&post.extra_properties),
+                posts_dsl::post_status.eq(&post.post_status),
+                posts_dsl::visibility.eq(&post.visibility),
+                posts_dsl::created_at.eq(&post.created_at),
+                posts_dsl::updated_at.eq(format!("{}", new_updated_at)),
+            )
+        ).execute(conn)?;
+        info!("updated post id {:?} (slug {:?}), rows affected: {}", post.id, post.slug, rows_updated);
+
+        use crate::schema::post_history::dsl as post_history_dsl;
+        diesel::insert_into(post_history_dsl::post_history)
+            .values(&NewPostHistory::from(original_post))
+            .execute(conn)?;
+        Ok(())
+    })?;
+
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .body(Body::empty())
+        .map_err(|e| {
+            error!("error building response {:?}", e);
+            MicropubError::internal("error building response")
+        })
+}
+
+fn replace_property(
+    conn: &mut SqliteConnection,
+    post: &mut Post,
+    key: &str,
+    values: &[serde_json::Value],
+) -> Result<(), MicropubError> {
+    info!("replacing {:?} of post with slug {:?}", key, post.slug);
+    match key {
+        "content" => set_content(post, values)?,
+        "content[html]" => {
+            post.content = Some(first_string(key, values)?);
+            post.content_type = Some("html".into());
+        },
+        "published" => {
+            let published = first_string(key, values)?;
+            post.created_at = post_util::normalize_published(&published)
+                .ok_or_else(|| MicropubError::invalid_request(format!("could not parse published date '{}'", published)))?;
+        },
+        "post-status" => post.post_status = allowed_value(key, values, POST_STATUSES)?,
+        "visibility" => post.visibility = allowed_value(key, values, VISIBILITIES)?,
+        "mp-slug" => {
+            let new_slug = post_util::normalize_slug(&first_string(key, values)?)
+                .map_err(MicropubError::invalid_request)?;
+            let existing: i64 = Post::by_slug_including_deleted(&new_slug)
+                .filter(crate::schema::posts::id.ne(post.id))
+                .count()
+                .get_result(conn)?;
+            if existing > 0 {
+                return Err(MicropubError::invalid_request(format!("slug '{}' is already in use", new_slug)));
             }
-        });
-        results.try_fold((), |_acc, r: Result<(), StatusCode>| r)?;
+            post.slug = new_slug;
+        },
+        "category" => {
+            use crate::schema::categories::dsl::*;
+            diesel::delete(
+                categories
+                    .filter(post_id.eq(post.id))
+            ).execute(conn)?;
+            add_categories(conn, post.id, &string_values(key, values)?)?;
+        },
+        "photo" => {
+            use crate::schema::photos::dsl::*;
+            diesel::delete(
+                photos
+                    .filter(post_id.eq(post.id))
+            ).execute(conn)?;
+            add_photos(conn, post.id, &photos_from_values(values)?)?;
+        },
+        "start" | "end" | "url" if post.entry_type == "event" => {
+            set_event_property(conn, post.id, key, Some(&first_string(key, values)?))?
+        },
+        "item" | "rating" | "best" | "worst" if post.entry_type == "review" => {
+            set_review_property(conn, post.id, key, Some(values))?
+        },
+        "location" | "checkin" => set_place(conn, post, key, Some(values))?,
+        k => {
+            let entry_type = post.entry_type.clone();
+            match optional_property(post, k) {
+                Some(field) => *field = Some(first_string(k, values)?),
+                None if is_extra_property(&entry_type, k) => update_extra_properties(post, |extra| {
+                    extra.insert(k.into(), serde_json::Value::Array(values.to_vec()));
+                }),
+                None => warn!("unhandled key for replace action: {:?}", k),
+            }
+        },
     }
+    Ok(())
+}
 
-    // handle deletions
-    if let Some(deletes) = json.get("delete") {
-        let mut results: Box<dyn Iterator<Item=Result<(), StatusCode>>> = 
-            if let Some(delete_as_array) = deletes.as_array() {
-                // handle deleting entire properties
-                Box::new(delete_as_array.iter().map(|key| {
-                    // TODO handle other properties here
-                    match key.as_str() {
-                        Some("category") => {
-                            db.run_txn(|conn| {
-                                use crate::schema::categories::dsl::*;
-                                diesel::delete(
-                                    categories
-                                        .filter(post_id.eq(post.id))
-                                ).execute(conn)?;
-                                Ok(())
-                            }).map_err(|e| e.into())
-                        },
-                        k => {
-                            warn!("unhandled key for delete action: {:?}", k);
-                            Ok(())
-                        },
+fn add_property(
+    conn: &mut SqliteConnection,
+    post: &mut Post,
+    key: &str,
+    values: &[serde_json::Value],
+) -> Result<(), MicropubError> {
+    info!("adding to {:?} of post with slug {:?}", key, post.slug);
+    match key {
+        "category" => add_categories(conn, post.id, &string_values(key, values)?)?,
+        "photo" => add_photos(conn, post.id, &photos_from_values(values)?)?,
+        // we only store a single value for these, so they can only be added when they're unset
+        "content" | "content[html]" if post.content.is_none() => replace_property(conn, post, key, values)?,
+        "content" | "content[html]" | "published" | "mp-slug" | "post-status" | "visibility" => {
+            return Err(MicropubError::invalid_request(format!("'{}' already has a value and only supports one", key)));
+        },
+        "start" | "end" | "url" if post.entry_type == "event" => {
+            if event_property(conn, post.id, key)?.is_some() {
+                return Err(MicropubError::invalid_request(format!("'{}' already has a value and only supports one", key)));
+            }
+            set_event_property(conn, post.id, key, Some(&first_string(key, values)?))?
+        },
+        "item" | "rating" | "best" | "worst" if post.entry_type == "review" => {
+            let mut review: Review = Review::for_posts(&[post.id]).first(conn)?;
+            // a review always has an item
+            if key == "item" || review_number(&mut review, key).is_some() {
+                return Err(MicropubError::invalid_request(format!("'{}' already has a value and only supports one", key)));
+            }
+            set_review_property(conn, post.id, key, Some(values))?
+        },
+        "location" | "checkin" => {
+            if has_place(conn, post, key)? {
+                return Err(MicropubError::invalid_request(format!("'{}' already has a value and only supports one", key)));
+            }
+            set_place(conn, post, key, Some(values))?
+        },
+        k => {
+            let entry_type = post.entry_type.clone();
+            match optional_property(post, k) {
+                Some(field) if field.is_some() => {
+                    return Err(MicropubError::invalid_request(format!("'{}' already has a value and only supports one", k)));
+                },
+                Some(field) => *field = Some(first_string(k, values)?),
+                None if is_extra_property(&entry_type, k) => update_extra_properties(post, |extra| {
+                    match extra.entry(k).or_insert_with(|| json!([])) {
+                        serde_json::Value::Array(existing) => existing.extend_from_slice(values),
+                        other => *other = serde_json::Value::Array(values.to_vec()),
                     }
-                }))
-            } else if let Some(delete_as_obj) = deletes.as_object() {
-                Box::new(delete_as_obj.into_iter().map(|(key, vs)| {
-                    match key.as_str() {
-                        "category" => {
-                            db.run_txn(|conn| {
-                                if let Some(category_values) = vs.as_array().map(|a| a.iter().flat_map(|v| v.as_str()).collect::<Vec<&str>>()) {
-                                    use crate::schema::categories::dsl::*;
-                                    diesel::delete(
-                                        categories
-                                            .filter(post_id.eq(post.id))
-                                            .filter(category.eq_any(&category_values))
-                                    ).execute(conn)?;
-                                }
-                                Ok(())
-                            }).map_err(|e| e.into())
-                        },
-                        k => {
-                            warn!("unhandled key for delete action: {:?}", k);
-                            Ok(())
-                        },
+                }),
+                None => warn!("unhandled key for add action: {:?}", k),
+            }
+        },
+    }
+    Ok(())
+}
+
+fn delete_property(
+    conn: &mut SqliteConnection,
+    post: &mut Post,
+    key: &str,
+) -> Result<(), MicropubError> {
+    info!("deleting {:?} of post with slug {:?}", key, post.slug);
+    match key {
+        "content" | "content[html]" => {
+            post.content = None;
+            post.content_type = None;
+        },
+        "published" | "mp-slug" => {
+            return Err(MicropubError::invalid_request(format!("'{}' cannot be deleted", key)));
+        },
+        "post-status" => post.post_status = "published".into(),
+        "visibility" => post.visibility = "public".into(),
+        "category" => {
+            use crate::schema::categories::dsl::*;
+            diesel::delete(
+                categories
+                    .filter(post_id.eq(post.id))
+            ).execute(conn)?;
+        },
+        "photo" => {
+            use crate::schema::photos::dsl::*;
+            diesel::delete(
+                photos
+                    .filter(post_id.eq(post.id))
+            ).execute(conn)?;
+        },
+        "start" | "end" | "url" if post.entry_type == "event" => set_event_property(conn, post.id, key, None)?,
+        "item" | "rating" | "best" | "worst" if post.entry_type == "review" => {
+            set_review_property(conn, post.id, key, None)?
+        },
+        "location" | "checkin" => set_place(conn, post, key, None)?,
+        k => {
+            let entry_type = post.entry_type.clone();
+            match optional_property(post, k) {
+                Some(field) => *field = None,
+                None if is_extra_property(&entry_type, k) => update_extra_properties(post, |extra| {
+                    extra.remove(k);
+                }),
+                None => warn!("unhandled key for delete action: {:?}", k),
+            }
+        },
+    }
+    Ok(())
+}
+
+fn delete_property_values(
+    conn: &mut SqliteConnection,
+    post: &mut Post,
+    key: &str,
+    values: &[serde_json::Value],
+) -> Result<(), MicropubError> {
+    info!("deleting values of {:?} of post with slug {:?}", key, post.slug);
+    match key {
+        "category" => {
+            let category_values = string_values(key, values)?;
+            use crate::schema::categories::dsl::*;
+            diesel::delete(
+                categories
+                    .filter(post_id.eq(post.id))
+                    .filter(category.eq_any(&category_values))
+            ).execute(conn)?;
+        },
+        "photo" => {
+            // photos may be identified by their url alone or the same object used to add them
+            let urls: Vec<String> = photos_from_values(values)?
+                .into_iter()
+                .map(|p| p.url)
+                .collect();
+            use crate::schema::photos::dsl::*;
+            diesel::delete(
+                photos
+                    .filter(post_id.eq(post.id))
+                    .filter(url.eq_any(&urls))
+            ).execute(conn)?;
+        },
+        "start" | "end" | "url" if post.entry_type == "event" => {
+            let to_delete = string_values(key, values)?;
+            // times are compared as stored, so a start or end must be given in UTC to delete it
+            if event_property(conn, post.id, key)?.is_some_and(|v| to_delete.contains(&v.as_str())) {
+                set_event_property(conn, post.id, key, None)?;
+            }
+        },
+        "item" if post.entry_type == "review" => return Err(MicropubError::invalid_request("'item' cannot be deleted")),
+        "rating" | "best" | "worst" if post.entry_type == "review" => {
+            let mut review: Review = Review::for_posts(&[post.id]).first(conn)?;
+            let current = *review_number(&mut review, key);
+            let to_delete: Vec<f64> = values.iter()
+                .filter_map(|v| v.as_f64().or_else(|| v.as_str().and_then(|n| n.parse().ok())))
+                .collect();
+            if current.is_some_and(|n| to_delete.contains(&n)) {
+                set_review_property(conn, post.id, key, None)?;
+            }
+        },
+        "location" | "checkin" => {
+            return Err(MicropubError::invalid_request(format!("'{}' can only be deleted as a whole", key)));
+        },
+        k => {
+            let entry_type = post.entry_type.clone();
+            match optional_property(post, k) {
+                Some(field) => {
+                    let to_delete = string_values(k, values)?;
+                    if field.as_deref().is_some_and(|v| to_delete.contains(&v)) {
+                        *field = None;
                     }
-                }))
+                },
+                None if is_extra_property(&entry_type, k) => update_extra_properties(post, |extra| {
+                    if let Some(serde_json::Value::Array(existing)) = extra.get_mut(k) {
+                        existing.retain(|v| !values.contains(v));
+                        if existing.is_empty() {
+                            extra.remove(k);
+                        }
+                    }
+                }),
+                None => warn!("unhandled key for delete action: {:?}", k),
+            }
+        },
+    }
+    Ok(())
+}
+
+/// The start, end or url of an event post, from its row in events.
+fn event_property(conn: &mut SqliteConnection, post_id: i32, key: &str) -> Result<Option<String>, MicropubError> {
+    let event: Event = Event::for_posts(&[post_id]).first(conn)?;
+    Ok(match key {
+        "start" => Some(event.starts_at),
+        "end" => event.ends_at,
+        _ => event.url,
+    })
+}
+
+/// Sets, or with None clears, the start, end or url of an event post. Times are stored in UTC as
+/// they are on create, and an event can't be left without a start.
+fn set_event_property(
+    conn: &mut SqliteConnection,
+    post_id: i32,
+    key: &str,
+    value: Option<&str>,
+) -> Result<(), MicropubError> {
+    let normalize = |time: &str| {
+        post_util::normalize_published(time)
+            .ok_or_else(|| MicropubError::invalid_request(format!("invalid {} '{}'", key, time)))
+    };
+    let event = events::table.filter(events::post_id.eq(post_id));
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    match key {
+        "start" => {
+            let start = value.ok_or_else(|| MicropubError::invalid_request("'start' cannot be deleted"))?;
+            diesel::update(event)
+                .set((events::starts_at.eq(normalize(start)?), events::updated_at.eq(now)))
+                .execute(conn)?;
+        },
+        "end" => {
+            diesel::update(event)
+                .set((events::ends_at.eq(value.map(normalize).transpose()?), events::updated_at.eq(now)))
+                .execute(conn)?;
+        },
+        _ => {
+            diesel::update(event)
+                .set((events::url.eq(value), events::updated_at.eq(now)))
+                .execute(conn)?;
+        },
+    }
+    Ok(())
+}
+
+/// The rating, best or worst of a review.
+fn review_number<'a>(review: &'a mut Review, key: &str) -> &'a mut Option<f64> {
+    match key {
+        "rating" => &mut review.rating,
+        "best" => &mut review.best,
+        _ => &mut review.worst,
+    }
+}
+
+/// Sets, or with None clears, the item, rating, best or worst of a review post. Values are parsed
+/// as they are on create, and the rating must stay within best and worst.
+fn set_review_property(
+    conn: &mut SqliteConnection,
+    post_id: i32,
+    key: &str,
+    values: Option<&[serde_json::Value]>,
+) -> Result<(), MicropubError> {
+    let mut review: Review = Review::for_posts(&[post_id]).first(conn)?;
+    match (key, values) {
+        ("item", Some(values)) => {
+            let item = review_item(property_value(key, values)?)
+                .ok_or_else(|| MicropubError::invalid_request("'item' must be a name, url or object"))?;
+            review.item_name = item.name;
+            review.item_url = item.url;
+        },
+        ("item", None) => return Err(MicropubError::invalid_request("'item' cannot be deleted")),
+        (_, Some(values)) => {
+            let n = number_value(property_value(key, values)?)
+                .ok_or_else(|| MicropubError::invalid_request(format!("'{}' must be a number", key)))?;
+            *review_number(&mut review, key) = Some(n);
+        },
+        (_, None) => *review_number(&mut review, key) = None,
+    }
+    if let Some(rating) = review.rating
+        && !rating_in_range(rating, review.best, review.worst)
+    {
+        return Err(MicropubError::invalid_request("'rating' must be between 'worst' and 'best'"));
+    }
+
+    diesel::update(reviews::table.filter(reviews::post_id.eq(post_id)))
+        .set((
+            reviews::item_name.eq(&review.item_name),
+            reviews::item_url.eq(&review.item_url),
+            reviews::rating.eq(review.rating),
+            reviews::best.eq(review.best),
+            reviews::worst.eq(review.worst),
+            reviews::updated_at.eq(chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Whether a post has a checkin or location. An event's location is kept with the event.
+fn has_place(conn: &mut SqliteConnection, post: &Post, key: &str) -> Result<bool, MicropubError> {
+    if key == "location" && post.entry_type == "event" {
+        let event: Event = Event::for_posts(&[post.id]).first(conn)?;
+        return Ok(event.location.is_some());
+    }
+    let count: i64 = Location::for_posts(&[post.id])
+        .filter(locations::property.eq(key))
+        .count()
+        .get_result(conn)?;
+    Ok(count > 0)
+}
+
+/// Replaces, or with None deletes, the checkin or location of a post. Values are parsed as they
+/// are on create, and an event's location is kept with the event as a label.
+fn set_place(
+    conn: &mut SqliteConnection,
+    post: &Post,
+    key: &str,
+    values: Option<&[serde_json::Value]>,
+) -> Result<(), MicropubError> {
+    let place = values.map(|values| {
+        place_from_value(property_value(key, values)?)
+            .ok_or_else(|| MicropubError::invalid_request(format!("'{}' must be a geo: URI, url, name or object", key)))
+    }).transpose()?;
+
+    if key == "location" && post.entry_type == "event" {
+        diesel::update(events::table.filter(events::post_id.eq(post.id)))
+            .set((
+                events::location.eq(place.and_then(Place::label)),
+                events::updated_at.eq(chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()),
+            ))
+            .execute(conn)?;
+        return Ok(());
+    }
+
+    diesel::delete(
+        locations::table
+            .filter(locations::post_id.eq(post.id))
+            .filter(locations::property.eq(key))
+    ).execute(conn)?;
+    if let Some(place) = place {
+        diesel::insert_into(locations::table)
+            .values(NewLocation {
+                post_id: post.id,
+                property: key,
+                name: place.name.as_deref(),
+                url: place.url.as_deref(),
+                latitude: place.latitude,
+                longitude: place.longitude,
+            })
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Update values as a MicropubPropertyValue, so they can be parsed the same way as a create's.
+fn property_value(key: &str, values: &[serde_json::Value]) -> Result<MicropubPropertyValue, MicropubError> {
+    serde_json::from_value(serde_json::Value::Array(values.to_vec()))
+        .map_err(|_| MicropubError::invalid_request(format!("unsupported values for '{}'", key)))
+}
+
+/// Applies `f` to the post's extra properties, i.e. those we don't have a column for.
+fn update_extra_properties<F>(post: &mut Post, f: F)
+where F: FnOnce(&mut serde_json::Map<String, serde_json::Value>) {
+    let mut extra = parse_extra_properties(post.extra_properties.as_deref());
+    f(&mut extra);
+    post.extra_properties = extra_properties_json(&extra);
+}
+
+/// The post field for single valued, optional properties that don't need special handling.
+fn optional_property<'a>(post: &'a mut Post, key: &str) -> Option<&'a mut Option<String>> {
+    match key {
+        "name" => Some(&mut post.name),
+        "summary" => Some(&mut post.summary),
+        "bookmark-of" => Some(&mut post.bookmark_of),
+        "in-reply-to" => Some(&mut post.in_reply_to),
+        "like-of" => Some(&mut post.like_of),
+        "repost-of" => Some(&mut post.repost_of),
+        _ => None,
+    }
+}
+
+fn set_content(post: &mut Post, values: &[serde_json::Value]) -> Result<(), MicropubError> {
+    match values.first() {
+        Some(serde_json::Value::String(c)) => {
+            post.content_type = None;
+            post.content = Some(c.into());
+        },
+        // an object with "html" (or "markdown") as a key changes the content type too
+        Some(serde_json::Value::Object(c)) => {
+            if let Some(html_content) = c.get("html").and_then(|v| v.as_str()) {
+                post.content = Some(html_content.into());
+                post.content_type = Some("html".into());
+            } else if let Some(markdown_content) = c.get("markdown").and_then(|v| v.as_str()) {
+                post.content = Some(markdown_content.into());
+                post.content_type = Some("markdown".into());
             } else {
-                Box::new(vec![Err(StatusCode::BAD_REQUEST)].into_iter())
-            };
-        results.try_fold((), |_acc, r: Result<(), StatusCode>| r)?;
+                warn!("unhandled content structure: {:?}", values);
+                return Err(MicropubError::invalid_request("'content' objects must have an 'html' or 'markdown' value"));
+            }
+        },
+        _ => {
+            warn!("unhandled content structure: {:?}", values);
+            return Err(MicropubError::invalid_request("'content' must be a string or an object"));
+        },
     }
+    Ok(())
+}
 
-    // TODO consider saving copies of the old post in a history table before updating? Inserting a
-    // new version into same table?
-    db.run_txn(|conn| {
-        let new_updated_at = Local::now().with_timezone(&site_config.micropub.current_timezone_offset)
+/// The values of a property in an update request, which must be an array.
+fn property_values<'a>(key: &str, vs: &'a serde_json::Value) -> Result<&'a [serde_json::Value], MicropubError> {
+    vs.as_array()
+        .map(Vec::as_slice)
+        .ok_or_else(|| {
+            error!("values for {:?} were not an array. {}", key, vs);
+            MicropubError::invalid_request(format!("values for '{}' must be an array", key))
+        })
+}
+
+fn first_string(key: &str, values: &[serde_json::Value]) -> Result<String, MicropubError> {
+    values.first()
+        .and_then(|v| v.as_str())
+        .map(String::from)
+        .ok_or_else(|| MicropubError::invalid_request(format!("'{}' must have a string value", key)))
+}
+
+/// The first value of a property that only has a fixed set of allowed values, e.g. visibility.
+fn allowed_value(key: &str, values: &[serde_json::Value], allowed: &[&str]) -> Result<String, MicropubError> {
+    let value = first_string(key, values)?;
+    if allowed.contains(&value.as_str()) {
+        Ok(value)
+    } else {
+        Err(MicropubError::invalid_request(format!("unsupported {} '{}'", key, value)))
+    }
+}
+
+fn string_values<'a>(key: &str, values: &'a [serde_json::Value]) -> Result<Vec<&'a str>, MicropubError> {
+    values.iter()
+        .map(|v| v.as_str().ok_or_else(|| MicropubError::invalid_request(format!("values for '{}' must be strings", key))))
+        .collect()
+}
+
+/// Photos from update values, which are either urls or objects with a url "value" and "alt" text.
+fn photos_from_values(values: &[serde_json::Value]) -> Result<Vec<Photo>, MicropubError> {
+    values.iter()
+        .map(|v| match v {
+            serde_json::Value::String(url) => Ok(Photo { url: url.clone(), alt: None }),
+            serde_json::Value::Object(photo) => {
+                let url = photo.get("value")
+                    .and_then(|u| u.as_str())
+                    .ok_or_else(|| MicropubError::invalid_request("'photo' objects must have a 'value'"))?;
+                let alt = photo.get("alt").and_then(|a| a.as_str()).map(String::from);
+                Ok(Photo { url: url.into(), alt })
+            },
+            _ => Err(MicropubError::invalid_request("'photo' values must be urls or objects")),
+        })
+        .collect()
+}
+
+fn add_categories(conn: &mut SqliteConnection, post_id: i32, new_categories: &[&str]) -> Result<(), diesel::result::Error> {
+    for c in new_categories {
+        // categories are unique per post, adding one that's already there is a no-op
+        diesel::insert_or_ignore_into(categories::table)
+            .values(NewCategory { post_id, category: c })
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+fn add_photos(conn: &mut SqliteConnection, post_id: i32, new_photos: &[Photo]) -> Result<(), diesel::result::Error> {
+    for p in new_photos {
+        diesel::insert_into(photos::table)
+            .values(NewPhoto {
+                post_id,
+                url: p.url.as_str(),
+                alt: p.alt.as_deref(),
+            })
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Handles the delete and undelete actions. Posts are soft-deleted by setting `deleted_at` so that
+/// they can be restored later and so that their URLs can respond with 410 Gone. A snapshot of the
+/// post prior to each transition is written to post_history.
+async fn handle_delete(
+    db: Arc<MicropubDB>,
+    site_config: Arc<crate::MicropubSiteConfig>,
+    url: Option<&str>,
+    delete: bool,
+) -> Result<Response<Body>, MicropubError> {
+    let url = url.ok_or_else(|| {
+        error!("delete/undelete request did not contain 'url'");
+        MicropubError::invalid_request("request is missing 'url'")
+    })?;
+    let post_slug = slug_from_url(&site_config, url)?;
+    let new_deleted_at = if delete {
+        let now = Local::now().with_timezone(&site_config.micropub.current_timezone_offset)
             .format("%Y-%m-%d %H:%M:%S");
+        Some(format!("{}", now))
+    } else {
+        None
+    };
+
+    db.run_txn(|conn| {
+        let post: Post = Post::by_slug_including_deleted(post_slug)
+            .first::<Post>(conn)?;
+        if post.deleted_at.is_some() == delete {
+            info!("post with slug {:?} is already in the requested state (deleted: {})", post.slug, delete);
+            return Ok(());
+        }
 
         use crate::schema::posts::dsl::*;
         let rows_updated = diesel::update(
             posts
                 .filter(id.eq(post.id))
         ).set(
-            (
-                name.eq(post.name),
-                content.eq(post.content),
-                content_type.eq(post.content_type),
-                updated_at.eq(format!("{}", new_updated_at)),
-            )
+            deleted_at.eq(new_deleted_at)
         ).execute(conn)?;
-        info!("updated post id {:?} (slug {:?}), rows affected: {}", post.id, post.slug, rows_updated);
+        info!("set deleted: {} for post id {:?} (slug {:?}), rows affected: {}", delete, post.id, post.slug, rows_updated);
 
         use crate::schema::post_history::dsl as post_history_dsl;
         diesel::insert_into(post_history_dsl::post_history)
-            .values(&NewPostHistory::from(original_post))
+            .values(&NewPostHistory::from(post))
             .execute(conn)?;
         Ok(())
     })?;
@@ -803,10 +2002,311 @@ async fn handle_update(
         .body(Body::empty())
         .map_err(|e| {
             error!("error building response {:?}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            MicropubError::internal("error building response")
         })
 }
 
+/// The scope a token must have been granted to perform the given action.
+fn scope_for_action(action: &str) -> Option<&'static str> {
+    match action {
+        "update" => Some("update"),
+        "delete" | "undelete" => Some("delete"),
+        _ => None,
+    }
+}
+
+/// Handles q=source. With a `url` this returns the properties of that post. Without one it lists
+/// posts (newest first) as `{"items": [...]}`, paginated by `limit` and `offset` (or the `after`
+/// cursor from a previous page's `paging`) and optionally filtered by `post-type`. Both forms
+/// honour `properties[]`.
+fn handle_source_query(
+    site_config: &crate::MicropubSiteConfig,
+    query: &[(String, String)],
+    db: &MicropubDB,
+) -> Result<String, MicropubError> {
+    let properties: Vec<String> = query.iter()
+        .filter(|(key, _value)| key == "properties" || key == "properties[]")
+        .map(|(_key, value)| value.clone())
+        .collect();
+    let mut conn = db.dbconn()?;
+
+    if let Some(url) = query_param(query, "url") {
+        let decoded_url = decode(url)
+            .map_err(|e| {
+                warn!("error decoding url: {}, error: {}", url, e);
+                MicropubError::invalid_request("could not decode 'url'")
+            })?;
+        let slug = slug_from_url(site_config, &decoded_url)?;
+
+        let post = Post::by_slug(slug)
+            .first::<Post>(&mut conn)
+            .map_err(|e| db.handle_errors(e))?;
+        let micropub_form = micropub_forms_for_posts(&mut conn, vec![post])
+            .map_err(|e| db.handle_errors(e))?
+            .pop()
+            .expect("one form is returned per post");
+
+        return micropub_form.to_properties_json(&properties)
+            .map_err(|e| {
+                error!("error producing properties json: {:?}", e);
+                MicropubError::internal("error producing properties json")
+            });
+    }
+
+    let limit = match query_param(query, "limit") {
+        Some(l) => l.parse::<i64>()
+            .ok()
+            .filter(|l| *l > 0)
+            .ok_or_else(|| MicropubError::invalid_request("'limit' must be a positive integer"))?
+            .min(MAX_SOURCE_LIST_LIMIT),
+        None => DEFAULT_SOURCE_LIST_LIMIT,
+    };
+    // `after` is the opaque cursor we hand out in `paging`, which happens to be an offset
+    let offset = match query_param(query, "after").or_else(|| query_param(query, "offset")) {
+        Some(o) => o.parse::<i64>()
+            .ok()
+            .filter(|o| *o >= 0)
+            .ok_or_else(|| MicropubError::invalid_request("'offset' and 'after' must be non-negative integers"))?,
+        None => 0,
+    };
+    let posts_query = match query_param(query, "post-type") {
+        Some(post_type) => Post::by_post_type(post_type)
+            .ok_or_else(|| MicropubError::invalid_request(format!("unsupported post-type '{}'", post_type)))?,
+        None => Post::all(),
+    };
+
+    // fetch one extra post to find out whether there is another page
+    let mut posts = posts_query
+        .limit(limit + 1)
+        .offset(offset)
+        .load::<Post>(&mut conn)
+        .map_err(|e| db.handle_errors(e))?;
+    let has_more = posts.len() as i64 > limit;
+    posts.truncate(limit as usize);
+
+    let items: Vec<serde_json::Value> = micropub_forms_for_posts(&mut conn, posts)
+        .map_err(|e| db.handle_errors(e))?
+        .iter()
+        .map(|form| {
+            let mut item = form.to_properties(&properties);
+            if (properties.is_empty() || properties.iter().any(|p| p == "url"))
+                && let Some(slug) = &form.slug
+                && let Some(props) = item.get_mut("properties").and_then(|p| p.as_object_mut())
+            {
+                props.insert("url".into(), json!(vec![site_config.micropub.site_url(slug)]));
+            }
+            item
+        })
+        .collect();
+
+    let mut result = json!({
+        "items": items,
+    });
+    if has_more {
+        result.as_object_mut()
+            .expect("is object")
+            .insert("paging".into(), json!({"after": (offset + limit).to_string()}));
+    }
+
+    Ok(result.to_string())
+}
+
+/// Loads the categories and photos for each post and returns the posts as MicropubForms, in the
+/// same order as `posts`.
+fn micropub_forms_for_posts(
+    conn: &mut SqliteConnection,
+    posts: Vec<Post>,
+) -> Result<Vec<MicropubForm>, diesel::result::Error> {
+    let post_ids: Vec<i32> = posts.iter().map(|p| p.id).collect();
+
+    use crate::schema::categories::dsl as category_dsl;
+    let tags: Vec<(i32, String)> = category_dsl::categories
+        .select((category_dsl::post_id, category_dsl::category))
+        .filter(category_dsl::post_id.eq_any(&post_ids))
+        .get_results(conn)?;
+    let mut tags_by_post: HashMap<i32, Vec<String>> = HashMap::new();
+    for (post_id, tag) in tags {
+        tags_by_post.entry(post_id).or_default().push(tag);
+    }
+
+    use crate::schema::photos::dsl as photos_dsl;
+    let photos: Vec<(i32, String, Option<String>)> = photos_dsl::photos
+        .select((photos_dsl::post_id, photos_dsl::url, photos_dsl::alt))
+        .filter(photos_dsl::post_id.eq_any(&post_ids))
+        .order_by(photos_dsl::id.asc())
+        .get_results(conn)?;
+    let mut photos_by_post: HashMap<i32, Vec<(String, Option<String>)>> = HashMap::new();
+    for (post_id, url, alt) in photos {
+        photos_by_post.entry(post_id).or_default().push((url, alt));
+    }
+
+    let events_by_post: HashMap<i32, Event> = Event::for_posts(&post_ids)
+        .load::<Event>(conn)?
+        .into_iter()
+        .map(|e| (e.post_id, e))
+        .collect();
+
+    let reviews_by_post: HashMap<i32, Review> = Review::for_posts(&post_ids)
+        .load::<Review>(conn)?
+        .into_iter()
+        .map(|r| (r.post_id, r))
+        .collect();
+
+    let mut objects_by_post: HashMap<i32, Vec<NestedObject>> = HashMap::new();
+    for object in PostObject::for_posts(&post_ids).load::<PostObject>(conn)? {
+        match serde_json::from_str(&object.object) {
+            Ok(value) => objects_by_post.entry(object.post_id).or_default().push(NestedObject {
+                property: object.property,
+                value,
+            }),
+            Err(e) => error!("skipping malformed post object {}: {:?}", object.id, e),
+        }
+    }
+
+    let mut locations_by_post: HashMap<i32, Vec<Location>> = HashMap::new();
+    for location in Location::for_posts(&post_ids).load::<Location>(conn)? {
+        locations_by_post.entry(location.post_id).or_default().push(location);
+    }
+
+    Ok(posts.iter().map(|post| {
+        let mut form = MicropubForm::from_post(
+            post,
+            tags_by_post.get(&post.id).map(Vec::as_slice).unwrap_or(&[]),
+            photos_by_post.get(&post.id).map(Vec::as_slice).unwrap_or(&[]),
+        );
+        form.event = events_by_post.get(&post.id).map(EventProperties::from_event);
+        form.review = reviews_by_post.get(&post.id).map(ReviewProperties::from_review);
+        form.objects = objects_by_post.remove(&post.id).unwrap_or_default();
+        for location in locations_by_post.remove(&post.id).unwrap_or_default() {
+            match location.property.as_str() {
+                "checkin" => form.checkin = Some(Place::from_location(&location)),
+                _ => form.location = Some(Place::from_location(&location)),
+            }
+        }
+        form
+    }).collect())
+}
+
+fn query_param<'a>(query: &'a [(String, String)], key: &str) -> Option<&'a String> {
+    query.iter().find_map(|(k, value)| {
+        if k == key {
+            Some(value)
+        } else {
+            None
+        }
+    })
+}
+
+/// Escapes the wildcard characters of a LIKE pattern using '\\' as the escape character.
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Strips the configured host website from a post URL to get the post's slug.
+pub(crate) fn slug_from_url<'a>(site_config: &crate::MicropubSiteConfig, url: &'a str) -> Result<&'a str, MicropubError> {
+    url.strip_prefix(site_config.micropub.host_website.as_str())
+        .ok_or_else(|| {
+            error!("provided url {:?} did not contain host website prefix {:?}", url, site_config.micropub.host_website);
+            MicropubError::invalid_request(format!("'{}' is not a url on this site", url))
+        })
+}
+
+/// Checks for and verifies the access token, given either as a bearer token in the Authorization
+/// header or as an `access_token` parameter of the request, returning the token endpoint's
+/// response if the token is valid and belongs to this site. Requests may not use both.
+async fn authorize(
+    http_client: reqwest::Client,
+    site_config: Arc<crate::MicropubSiteConfig>,
+    db: &MicropubDB,
+    token_cache: &TokenCache,
+    headers: &http::header::HeaderMap,
+    access_token: Option<&str>,
+) -> Result<TokenValidateResponse, MicropubError> {
+    let auth = request_auth(headers, access_token)?;
+    authorize_token(http_client, site_config, db, token_cache, &auth).await
+}
+
+/// The Authorization header value to verify, built from the access_token parameter if the
+/// request doesn't have the header.
+fn request_auth(
+    headers: &http::header::HeaderMap,
+    access_token: Option<&str>,
+) -> Result<String, MicropubError> {
+    let header_auth: Option<&str> = headers.get(header::AUTHORIZATION)
+        .map(|h| h.to_str())
+        .transpose()
+        .map_err(|e| {
+            error!("error getting authorization header ascii contents: {:?}", e);
+            MicropubError::unauthorized("malformed authorization header")
+        })?;
+
+    match (header_auth, access_token) {
+        (Some(_), Some(_)) => Err(access_token_sent_twice()),
+        (Some(header_auth), None) => Ok(header_auth.to_string()),
+        (None, Some(token)) => Ok(format!("Bearer {}", token)),
+        (None, None) => {
+            warn!("unauthorized micropub request - missing access token");
+            Err(MicropubError::unauthorized("missing access token"))
+        },
+    }
+}
+
+/// Verifies an Authorization header value (`Bearer <token>`), returning the token endpoint's
+/// response if the token is valid and belongs to this site.
+pub(crate) async fn authorize_token(
+    http_client: reqwest::Client,
+    site_config: Arc<crate::MicropubSiteConfig>,
+    db: &MicropubDB,
+    token_cache: &TokenCache,
+    auth: &str,
+) -> Result<TokenValidateResponse, MicropubError> {
+    let validate_response = verify_auth(
+        http_client,
+        site_config.clone(),
+        db,
+        token_cache,
+        auth
+    ).await?;
+
+    if validate_response.me != site_config.micropub.host_website {
+        error!(
+            "mismatched authorization: me: {} host_website: {}",
+           validate_response.me,
+           site_config.micropub.host_website
+        );
+        return Err(MicropubError::forbidden("the access token was not issued for this site"));
+    }
+
+    Ok(validate_response)
+}
+
+fn require_scope(validate_response: &TokenValidateResponse, scope: &'static str) -> Result<(), MicropubError> {
+    if validate_response.has_scope(scope) {
+        Ok(())
+    } else {
+        warn!("token is missing scope {:?}", scope);
+        Err(MicropubError::InsufficientScope(scope))
+    }
+}
+
+/// Returns the action and url from a form encoded action request (e.g. `action=delete&url=...`)
+/// or None if the form does not contain an action.
+fn action_from_form_bytes(b: &[u8]) -> Option<(String, Option<String>)> {
+    let mut action = None;
+    let mut url = None;
+    for (k, v) in parse(b) {
+        match &*k {
+            "action" => action = Some(v.into_owned()),
+            "url" => url = Some(v.into_owned()),
+            _ => (),
+        }
+    }
+
+    action.map(|a| (a, url))
+}
+
 pub async fn handle_query(
     http_client: reqwest::Client,
     config: Arc<serde_json::Value>,
@@ -814,9 +2314,14 @@ pub async fn handle_query(
     headers: axum::http::HeaderMap,
     query: Query<Vec<(String, String)>>,
     db: Arc<MicropubDB>,
-) -> Result<impl IntoResponse, StatusCode> {
+    token_cache: Arc<TokenCache>,
+) -> Result<impl IntoResponse, MicropubError> {
     // looking for ?q=config
-    info!("query: {:?}", query);
+    let logged_query: Vec<(&str, &str)> = query
+        .iter()
+        .map(|(k, v)| (k.as_str(), if k == "access_token" { "[redacted]" } else { v.as_str() }))
+        .collect();
+    info!("query: {:?}", logged_query);
     let is_query = query.iter().find_map(|(header, value)| {
         if header == "q" {
             Some(value)
@@ -825,125 +2330,82 @@ pub async fn handle_query(
         }
     });
     if let Some(q) = is_query {
-        // verify auth
-        if let Some(auth_val) = headers.get("Authorization") {
-            let auth: &str = auth_val.to_str()
-                .map_err(|e| {
-                    error!("failed to to_str() on auth_val: {:?}", e);
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })?;
-            let validate_response = verify_auth(
-                http_client,
-                site_config.clone(),
-                auth
-            ).await?;
-
-            if validate_response.me != site_config.micropub.host_website {
-                return Err(StatusCode::FORBIDDEN);
-            }
+        let access_token = query_param(&query, "access_token").map(String::as_str);
+        authorize(http_client, site_config.clone(), &db, &token_cache, &headers, access_token).await?;
 
-            match q.as_str() {
-                "config" => {
-                    // return media endpoint
-                    return Ok(config.to_string())
-                },
-                "source" => {
-                    // return properties requested (or all?) if url in query matches one the server
-                    // can provide.
-                    let url = query.iter().find_map(|(key, value)| {
-                        if key == "url" {
-                            Some(value)
-                        } else {
-                            None
-                        }
-                    });
-
-                    if let Some(url) = url {
-                        let decoded_url = decode(url)
-                            .map_err(|e| {
-                                warn!("error decoding url: {}, error: {}", url, e);
-                                StatusCode::BAD_REQUEST
-                            })?;
-                        if let Some(slug) = decoded_url.strip_prefix(site_config.micropub.host_website.as_str()) {
-                            // get post + categories + photos for the slug
-                            let mut conn = db.dbconn()?;
-
-                            let post = Post::by_slug(slug)
-                                .first::<Post>(&mut conn)
-                                .map_err(|e| db.handle_errors(e))?;
-
-                            use crate::schema::categories::dsl as category_dsl;
-                            let tags: Vec<String> = category_dsl::categories
-                                .select(category_dsl::category)
-                                .filter(category_dsl::post_id.eq(post.id))
-                                .get_results(&mut conn)
-                                .map_err(|e| db.handle_errors(e))?;
-
-                            use crate::schema::photos::dsl as photos_dsl;
-                            let photos: Vec<(String, Option<String>)> = photos_dsl::photos
-                                .select((photos_dsl::url, photos_dsl::alt))
-                                .filter(photos_dsl::post_id.eq(post.id))
-                                .get_results(&mut conn)
-                                .map_err(|e| db.handle_errors(e))?;
-
-                            let micropub_form = MicropubForm::from_post(&post, &tags, &photos);
-
-                            // TODO only return the properties requested
-                            return micropub_form.to_properties_json()
-                                    .map_err(|e| {
-                                        error!("error producing properties json: {:?}", e);
-                                        StatusCode::INTERNAL_SERVER_ERROR
-                                    })
-                        } else {
-                            warn!("bad request - failed to strip host website from decoded url: {}", decoded_url);
-                            return Err(StatusCode::BAD_REQUEST)
-                        }
-                    } else {
-                        warn!("bad request - url not found in request");
-                        return Err(StatusCode::BAD_REQUEST)
-                    }
-                },
-                _ => {
-                    warn!("bad request - passthrough query type: {}", q);
-                    return Err(StatusCode::BAD_REQUEST)
+        if !SUPPORTED_QUERIES.contains(&q.as_str()) {
+            warn!("bad request - passthrough query type: {}", q);
+            return Err(MicropubError::invalid_request(format!("unsupported query '{}'", q)))
+        }
+        match q.as_str() {
+            "config" => {
+                // return media endpoint
+                return Ok(config.to_string())
+            },
+            "syndicate-to" => {
+                return Ok(json!({
+                    "syndicate-to": site_config.micropub.syndicate_to,
+                }).to_string())
+            },
+            "category" => {
+                // optional prefix filter so clients can autocomplete tags. Only tags of posts
+                // that are shown on the site are offered, the same as Post::published_by_slug
+                // without private posts.
+                let filter = query_param(&query, "filter");
+
+                let mut conn = db.dbconn()?;
+                use crate::schema::categories::dsl as category_dsl;
+                use crate::schema::posts::dsl as posts_dsl;
+                let mut categories_query = category_dsl::categories
+                    .select(category_dsl::category)
+                    .filter(category_dsl::post_id.eq_any(
+                        posts_dsl::posts
+                            .select(posts_dsl::id)
+                            .filter(posts_dsl::deleted_at.is_null())
+                            .filter(posts_dsl::post_status.eq("published"))
+                            .filter(posts_dsl::visibility.ne("private"))
+                            .filter(posts_dsl::created_at.le(diesel::dsl::sql::<diesel::sql_types::Text>("CURRENT_TIMESTAMP")))
+                    ))
+                    .distinct()
+                    .order_by(category_dsl::category.asc())
+                    .into_boxed();
+                if let Some(prefix) = filter {
+                    categories_query = categories_query
+                        .filter(category_dsl::category.like(format!("{}%", escape_like(prefix))).escape('\\'));
                 }
+                let tags: Vec<String> = categories_query
+                    .load(&mut conn)
+                    .map_err(|e| db.handle_errors(e))?;
+
+                return Ok(json!({
+                    "categories": tags,
+                }).to_string())
+            },
+            "source" => {
+                return handle_source_query(&site_config, &query, &db)
+            },
+            _ => {
+                error!("query type {} is in SUPPORTED_QUERIES but isn't handled", q);
+                return Err(MicropubError::internal(format!("query '{}' is not implemented", q)))
             }
-        } else {
-            warn!("unauthorized micropub query - missing authorization header");
-            return Err(StatusCode::UNAUTHORIZED);
         }
     }
 
-    Err(StatusCode::NOT_FOUND)
+    Err(MicropubError::NotFound)
 }
 
 // TODO look at axum DefaultBodyLimit and adjust
 pub async fn handle_media_upload(
     http_client: reqwest::Client,
     db: Arc<MicropubDB>,
+    token_cache: Arc<TokenCache>,
     headers: axum::http::HeaderMap,
     mut multipart_data: Multipart,
     site_config: Arc<crate::MicropubSiteConfig>,
-) -> Result<impl IntoResponse, StatusCode> {
-    // verify auth
-    if let Some(auth_val) = headers.get("Authorization") {
-        let auth: &str = auth_val.to_str()
-            .map_err(|e| {
-                error!("failed to to_str() on auth_val: {:?}", e);
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
-
-        let validate_response = verify_auth(
-            http_client.clone(),
-            site_config.clone(),
-            auth
-        ).await?;
-
-        if validate_response.me != site_config.micropub.host_website {
-            return Err(StatusCode::FORBIDDEN);
-        }
-    } else {
-        return Err(StatusCode::UNAUTHORIZED);
+) -> Result<impl IntoResponse, MicropubError> {
+    let validate_response = authorize(http_client.clone(), site_config.clone(), &db, &token_cache, &headers, None).await?;
+    if !validate_response.has_scope("create") {
+        require_scope(&validate_response, "media")?;
     }
 
     // find Part that has the name 'file'
@@ -954,111 +2416,311 @@ pub async fn handle_media_upload(
             Some("file") => {
                 let filename: Option<String> = field.file_name().map(|s| s.into());
                 let content_type: Option<String> = field.content_type().map(|s| s.into());
-                let mut contents = field.bytes().await
+                let contents = field.bytes().await
                     .map_err(|e| {
                         error!("error reading request body: {:?}", e);
-                        MediaUploadError
+                        MicropubError::invalid_request("could not read uploaded file")
                     })?;
 
-                // Pass media contents through imagemagick's strip functionality to remove things
-                // like EXIF tags that might contain location or other private information.
-                // attempt to get format
-                let format = media_util::guess_format(&content_type.as_deref());
-                match format {
-                    // we think the content is some sort of image, strip it and reject the request
-                    // if the strip operation fails
-                    Some(f) => {
-                        info!("content-type: {}", f);
-                        info!("attempting to strip media starting with: {:?}", &contents[0..64]);
-                        info!("length of media: {}", contents.len());
-                        contents = media_util::strip_media(&contents, &f).map(bytes::Bytes::from)?;
-                    }
-                    // still attempt to strip but don't reject if we fail
-                    None => {
-                        let f = "jpg";
-                        match media_util::strip_media(&contents, f).map(bytes::Bytes::from) {
-                            Ok(c) => contents = c,
-                            Err(e) => {
-                                // log error but we don't need to reject the whole request at this
-                                // point because we don't know for sure the content type was
-                                // image... this is not great given that there could still be exif
-                                // data to stip in a non-image format and it could fail in this
-                                // branch.
-                                error!("error in stripping tags in unknown content-type: {:?}", e);
-                            }
-                        }
-                    }
-                };
+                let hex_digest = store_media(
+                    http_client,
+                    db,
+                    &site_config,
+                    MediaPart { filename, content_type, contents },
+                ).await?;
 
-                // PUT to rustyblobjectstore backend
-                // TODO make object store URL configurable
-                let resp = http_client.put(&site_config.blobject_store_base_uri)
-                    .body(contents)
-                    .send()
-                    .await
-                    .map_err(|e| {
-                        error!("error in PUT to rustyblobjectstore: {:?}", e);
-                        MediaUploadError
-                    })?;
+                return Ok((
+                    StatusCode::CREATED,
+                    // XXX the http crate forces header names to be lower case, even if you
+                    // pass in a string that contains upper case characters. The 1.x
+                    // standard says header names should be case insensitive and the 2.0
+                    // standard forces lower case I guess.  The problem is that Quill is
+                    // currently case sensitive and won't find the location header:
+                    // https://github.com/aaronpk/Quill/blob/cdbc6aa4f305529f618e19b5af31ed896fb0a673/lib/helpers.php#L123
+                    // A proxy may be needed to resolve this if a fix cannot be pushed to
+                    // the quill client.
+                    [(
+                        header::LOCATION,
+                        site_config.micropub.site_url(&format!("media/{}", hex_digest))
+                    )
+                    ],
+                ))
+            }
+            _ => {
+                // Do nothing as we didn't find the upload
+            }
+        }
+    }
+    // TODO handle Err response from next_field here?
+    // If we got here it was either an err or we didn't find the file upload
+    // No 'file' part found in multipart form
+    Err(MicropubError::invalid_request("request did not contain a 'file' part"))
+}
+
+/// A file uploaded as part of a multipart/form-data request.
+#[derive(Debug)]
+struct MediaPart {
+    filename: Option<String>,
+    content_type: Option<String>,
+    contents: bytes::Bytes,
+}
 
-                let status = resp.status();
-                if status != 201 && status != 200 {
-                    error!("unsuccessful response status from rustyblobjectstore: {:?}", status);
-                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+/// A file that has been stored in the blob store, to be recorded in the media table.
+#[derive(Debug)]
+struct UploadedMedia {
+    /// Key of the stored blob, which is served under /media/
+    hex_digest: String,
+    filename: Option<String>,
+    content_type: Option<String>,
+}
+
+/// Strip metadata from the uploaded media, store it in the blob store and record it in the media
+/// table.
+///
+/// Returns the hex digest of the stored blob, which is served under /media/.
+async fn store_media(
+    http_client: reqwest::Client,
+    db: Arc<MicropubDB>,
+    site_config: &crate::MicropubSiteConfig,
+    part: MediaPart,
+) -> Result<String, MicropubError> {
+    let uploaded = upload_media(http_client, site_config, part).await?;
+    let mut conn = db.dbconn()?;
+    insert_media(&mut conn, &uploaded)
+        .map_err(|e| {
+            error!("error inserting hex digest into media uploads: {:?}", e);
+            MicropubError::internal("database error")
+        })?;
+
+    Ok(uploaded.hex_digest)
+}
+
+/// Strip metadata from the uploaded media and store it in the blob store, without recording it in
+/// the media table.
+async fn upload_media(
+    http_client: reqwest::Client,
+    site_config: &crate::MicropubSiteConfig,
+    part: MediaPart,
+) -> Result<UploadedMedia, MicropubError> {
+    let MediaPart { filename, content_type, mut contents } = part;
+
+    // Pass media contents through imagemagick's strip functionality to remove things
+    // like EXIF tags that might contain location or other private information.
+    // attempt to get format
+    let format = media_util::guess_format(&content_type.as_deref());
+    match format {
+        // we think the content is some sort of image, strip it and reject the request
+        // if the strip operation fails
+        Some(f) => {
+            info!("content-type: {}", f);
+            info!("attempting to strip media starting with: {:?}", &contents[..contents.len().min(64)]);
+            info!("length of media: {}", contents.len());
+            contents = media_util::strip_media(&contents, &f).map(bytes::Bytes::from)?;
+        }
+        // still attempt to strip but don't reject if we fail
+        None => {
+            let f = "jpg";
+            match media_util::strip_media(&contents, f).map(bytes::Bytes::from) {
+                Ok(c) => contents = c,
+                Err(e) => {
+                    // log error but we don't need to reject the whole request at this
+                    // point because we don't know for sure the content type was
+                    // image... this is not great given that there could still be exif
+                    // data to stip in a non-image format and it could fail in this
+                    // branch.
+                    error!("error in stripping tags in unknown content-type: {:?}", e);
                 }
+            }
+        }
+    };
 
-                // get the key of the blob to construct the URL used for fetching
-                // record rustyblobjectstore response (the blobject key), create media table entry respond
-                // with media URL (micropub-rs needs to handle this still because we want to proxy the
-                // rustyblobjectstore backend).
-                let hex_digest = resp.text()
-                    .await
-                    .map_err(|e| {
-                        error!("failure to read response body from rustyblobjectstore: {:?}", e);
-                        MediaUploadError
-                    })?;
+    // PUT to rustyblobjectstore backend
+    let resp = http_client.put(&site_config.blobject_store_base_uri)
+        .body(contents)
+        .send()
+        .await
+        .map_err(|e| {
+            error!("error in PUT to rustyblobjectstore: {:?}", e);
+            MicropubError::internal("error storing media")
+        })?;
+
+    let status = resp.status();
+    if status != 201 && status != 200 {
+        error!("unsuccessful response status from rustyblobjectstore: {:?}", status);
+        return Err(MicropubError::internal("error storing media"));
+    }
+
+    // get the key of the blob to construct the URL used for fetching
+    // record rustyblobjectstore response (the blobject key), create media table entry respond
+    // with media URL (micropub-rs needs to handle this still because we want to proxy the
+    // rustyblobjectstore backend).
+    let hex_digest = resp.text()
+        .await
+        .map_err(|e| {
+            error!("failure to read response body from rustyblobjectstore: {:?}", e);
+            MicropubError::internal("error storing media")
+        })?;
+
+    Ok(UploadedMedia { hex_digest, filename, content_type })
+}
+
+fn insert_media(conn: &mut SqliteConnection, uploaded: &UploadedMedia) -> Result<(), diesel::result::Error> {
+    diesel::insert_into(media::table)
+        .values(&NewMediaUpload {
+            hex_digest: &uploaded.hex_digest,
+            filename: uploaded.filename.as_deref(),
+            content_type: uploaded.content_type.as_deref(),
+        })
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Reads a request body of up to `limit` bytes.
+async fn read_body(request: axum::extract::Request, limit: usize) -> Result<bytes::Bytes, MicropubError> {
+    axum::body::to_bytes(request.into_body(), limit)
+        .await
+        .map_err(|e| {
+            error!("error reading bytes from body: {:?}", e);
+            MicropubError::invalid_request(format!("could not read request body of up to {} bytes", limit))
+        })
+}
 
-                let new_media = NewMediaUpload {
-                    hex_digest: &hex_digest,
-                    filename: filename.as_deref(),
-                    content_type: content_type.as_deref(),
-                };
-                let mut conn = db.dbconn()?;
-                diesel::insert_into(media::table)
-                    .values(&new_media)
-                    .execute(&mut conn)
-                    .map_err(|e| {
-                        error!("error inserting hex digest into media uploads: {:?}", e);
-                        DBError::new()
-                    })?;
+/// Streams a multipart/form-data request body, limited to the route's DefaultBodyLimit.
+async fn multipart_from_request(request: axum::extract::Request) -> Result<Multipart, MicropubError> {
+    Multipart::from_request(request, &())
+        .await
+        .map_err(|e| MicropubError::invalid_request(e.body_text()))
+}
 
+fn access_token_sent_twice() -> MicropubError {
+    warn!("micropub request has more than one access token");
+    MicropubError::invalid_request(
+        "the access token must be sent in either the Authorization header or the request, not both",
+    )
+}
 
-                return Ok((
-                    StatusCode::CREATED,
-                    // XXX the http crate forces header names to be lower case, even if you
-                    // pass in a string that contains upper case characters. The 1.x
-                    // standard says header names should be case insensitive and the 2.0
-                    // standard forces lower case I guess.  The problem is that Quill is
-                    // currently case sensitive and won't find the location header:
-                    // https://github.com/aaronpk/Quill/blob/cdbc6aa4f305529f618e19b5af31ed896fb0a673/lib/helpers.php#L123
-                    // A proxy may be needed to resolve this if a fix cannot be pushed to
-                    // the quill client.
-                    [(
-                        header::LOCATION,
-                        format!("https://davidwilemski.com/media/{}", hex_digest) // TODO don't hardcode domain
-                    )
-                    ],
-                ))
+/// Reads the text fields of a multipart/form-data body up to its `access_token` field, for requests
+/// without an Authorization header. Files and more than MAX_UNAUTHORIZED_FORM_LENGTH bytes of text
+/// before the token are rejected, so that no more than that is read before it's verified.
+async fn read_multipart_access_token(
+    multipart: &mut Multipart,
+    fields: &mut Vec<(String, String)>,
+) -> Result<String, MicropubError> {
+    let missing_token = || {
+        warn!("unauthorized micropub request - missing access token before any files");
+        MicropubError::unauthorized("missing access token, it must be sent before any files")
+    };
+    let mut length = 0;
+    while let Some(mut field) = multipart.next_field().await
+        .map_err(|e| MicropubError::invalid_request(e.body_text()))?
+    {
+        if field.file_name().is_some() {
+            return Err(missing_token());
+        }
+        let name = field.name().unwrap_or_default().to_string();
+        let mut value = vec![];
+        while let Some(chunk) = field.chunk().await
+            .map_err(|e| MicropubError::invalid_request(e.body_text()))?
+        {
+            length += name.len() + chunk.len();
+            if length > MAX_UNAUTHORIZED_FORM_LENGTH {
+                return Err(missing_token());
             }
-            _ => {
-                // Do nothing as we didn't find the upload
+            value.extend_from_slice(&chunk);
+        }
+        let value = String::from_utf8(value)
+            .map_err(|_| MicropubError::invalid_request(format!("field '{}' is not valid utf-8", name)))?;
+        if name == "access_token" {
+            return Ok(value);
+        }
+        fields.push((name, value));
+    }
+    Err(missing_token())
+}
+
+/// Reads the rest of a multipart/form-data body into its text fields and `photo` file uploads. The
+/// access token has either been sent in the Authorization header or read already, so another one
+/// is rejected.
+async fn read_multipart(
+    multipart: &mut Multipart,
+    fields: &mut Vec<(String, String)>,
+    files: &mut Vec<MediaPart>,
+) -> Result<(), MicropubError> {
+    while let Some(field) = multipart.next_field().await
+        .map_err(|e| MicropubError::invalid_request(e.body_text()))?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        if field.file_name().is_some() {
+            if name != "photo" && name != "photo[]" {
+                warn!("ignoring unsupported file part {:?} in multipart create", name);
+                continue;
             }
+            let filename: Option<String> = field.file_name().map(|s| s.into());
+            let content_type: Option<String> = field.content_type().map(|s| s.into());
+            let contents = field.bytes()
+                .await
+                .map_err(|e| MicropubError::invalid_request(e.body_text()))?;
+            files.push(MediaPart { filename, content_type, contents });
+        } else if name == "access_token" {
+            return Err(access_token_sent_twice());
+        } else {
+            let value = field.text()
+                .await
+                .map_err(|e| MicropubError::invalid_request(e.body_text()))?;
+            fields.push((name, value));
         }
     }
-    // TODO handle Err response from next_field here?
-    // If we got here it was either an err or we didn't find the file upload
-    // No 'file' part found in multipart form
-    Err(StatusCode::BAD_REQUEST)
+
+    Ok(())
+}
+
+/// Create a post from the fields and files of a multipart/form-data body (see read_multipart).
+/// Text fields are handled as they would be in a form encoded create while `photo` file parts are
+/// uploaded to the media store first and added to the post's photos.
+///
+/// Returns slug string if successful
+async fn create_multipart_post(
+    http_client: reqwest::Client,
+    db: Arc<MicropubDB>,
+    site_config: Arc<crate::MicropubSiteConfig>,
+    mut fields: Vec<(String, String)>,
+    files: Vec<MediaPart>,
+    client_id: &str,
+) -> Result<String, MicropubError> {
+    // Validate the rest of the form before storing any media
+    let mut form = MicropubForm::from_form_bytes(encode_form(&fields).as_bytes()).map_err(|e| {
+        error!("{:?}", e);
+        MicropubError::invalid_request(e.to_string())
+    })?;
+
+    // The files are uploaded to the blob store first, but only recorded in the media table along
+    // with the post so that a post that fails to insert doesn't leave media rows behind.
+    let mut uploaded = Vec::with_capacity(files.len());
+    for part in files {
+        let media = upload_media(http_client.clone(), &site_config, part).await?;
+        let url = site_config.micropub.site_url(&format!("media/{}", media.hex_digest));
+        fields.push(("photo[]".into(), url.clone()));
+        form.photos.get_or_insert_with(Vec::new).push(Photo { url, alt: None });
+        uploaded.push(media);
+    }
+
+    // The uploaded files themselves live in the media store, the original blob records the form
+    // fields along with the resulting photo urls.
+    let original_body = bytes::Bytes::from(encode_form(&fields));
+    insert_post(db, form, original_body, client_id, &uploaded)
+}
+
+/// Removes any `access_token` fields, returning the first one's value.
+fn take_access_token(fields: &mut Vec<(String, String)>) -> Option<String> {
+    let token = fields.iter().find(|(k, _)| k == "access_token").map(|(_, v)| v.clone());
+    fields.retain(|(k, _)| k != "access_token");
+    token
+}
+
+fn encode_form(fields: &[(String, String)]) -> String {
+    url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(fields)
+        .finish()
 }
 
 /// Given an content type and body bytes, parse body and create post entry in the database.
@@ -1069,11 +2731,7 @@ pub async fn create_post(
     content_type: Option<&HeaderValue>,
     body: bytes::Bytes,
     client_id: &str,
-) -> Result<String, StatusCode> {
-        // .map_err(|e| {
-        //     error!("Error getting content type: {:?}", e);
-        //     StatusCode::INTERNAL_SERVER_ERROR
-        // })?;
+) -> Result<String, MicropubError> {
     let ct: String = content_type
         .map(move |c| {
             c.to_str()
@@ -1084,34 +2742,76 @@ pub async fn create_post(
     let form = if ct.to_lowercase().starts_with("application/json") {
         MicropubForm::from_json_bytes(&body.slice(..)).map_err(|e| {
             error!("{:?}", e);
-            ValidateResponseDeserializeError
+            MicropubError::invalid_request(e.to_string())
         })?
     } else {
         // x-www-form-urlencoded
         MicropubForm::from_form_bytes(&body.slice(..)).map_err(|e| {
             error!("{:?}", e);
-            ValidateResponseDeserializeError
+            MicropubError::invalid_request(e.to_string())
         })?
     };
 
-    let slug = match form.slug {
-        Some(ref s) => s.clone(),
-        None => post_util::get_slug(form.name.as_deref(), Local::now),
-    };
+    insert_post(db, form, body, client_id, &[])
+}
 
-    let new_post = NewPost {
-        name: form.name.as_deref(),
-        slug: &slug, // TODO support inputting slug as part of the Micropub document/form
-        entry_type: &form.h,
-        content: Some(&form.content),
-        content_type: form.content_type.as_deref(),
-        client_id: Some(client_id),
-        created_at: form.created_at.as_deref(),
-        updated_at: form.updated_at.as_deref(),
-        bookmark_of: form.bookmark_of.as_deref(),
+/// Insert a parsed post along with its categories, photos and syndication targets. `body` is
+/// kept in original_blobs as the record of the request, and `media` uploaded with it is recorded
+/// in the same transaction.
+fn insert_post(
+    db: Arc<MicropubDB>,
+    form: MicropubForm,
+    body: bytes::Bytes,
+    client_id: &str,
+    media: &[UploadedMedia],
+) -> Result<String, MicropubError> {
+    // event times are stored in UTC so that upcoming and past events can be told apart
+    let event_times = form.event.as_ref().map(|e| {
+        let start = post_util::normalize_published(&e.start)
+            .ok_or_else(|| MicropubError::invalid_request(format!("invalid start '{}'", e.start)))?;
+        let end = e.end.as_deref().map(|end| {
+            post_util::normalize_published(end)
+                .ok_or_else(|| MicropubError::invalid_request(format!("invalid end '{}'", end)))
+        }).transpose()?;
+        Ok::<_, MicropubError>((start, end))
+    }).transpose()?;
+
+    let base_slug = match form.slug {
+        Some(ref s) => post_util::normalize_slug(s).map_err(MicropubError::invalid_request)?,
+        None => post_util::get_slug(form.name.as_deref(), Local::now),
     };
+    let extra_properties = extra_properties_json(&form.extra_properties);
+    // published dates are stored in UTC, like the default of CURRENT_TIMESTAMP, so that scheduled
+    // posts can be compared against the current time
+    let created_at = form.created_at.as_deref().map(|published| {
+        post_util::normalize_published(published)
+            .ok_or_else(|| MicropubError::invalid_request(format!("could not parse published date '{}'", published)))
+    }).transpose()?;
 
     db.run_txn(|conn| {
+        for m in media {
+            insert_media(conn, m)?;
+        }
+        let slug = unique_slug(conn, &base_slug)?;
+        let new_post = NewPost {
+            name: form.name.as_deref(),
+            slug: &slug,
+            entry_type: &form.h,
+            content: Some(&form.content),
+            content_type: form.content_type.as_deref(),
+            client_id: Some(client_id),
+            created_at: created_at.as_deref(),
+            updated_at: form.updated_at.as_deref(),
+            bookmark_of: form.bookmark_of.as_deref(),
+            summary: form.summary.as_deref(),
+            in_reply_to: form.in_reply_to.as_deref(),
+            like_of: form.like_of.as_deref(),
+            repost_of: form.repost_of.as_deref(),
+            extra_properties: extra_properties.as_deref(),
+            post_status: form.post_status.as_deref(),
+            visibility: form.visibility.as_deref(),
+        };
+
         diesel::insert_into(posts::table)
             .values(&new_post)
             .execute(conn)?;
@@ -1156,35 +2856,182 @@ pub async fn create_post(
             }
         }
 
-        Ok(())
-    })?;
+        let new_syndications: Vec<NewSyndication> = form
+            .syndicate_to
+            .iter()
+            .map(|target| NewSyndication {
+                post_id,
+                target: target.as_str(),
+            })
+            .collect();
+
+        for s in new_syndications {
+            diesel::insert_into(syndications::table)
+                .values(s)
+                .execute(conn)?;
+        }
+
+        if let Some(ref event) = form.event
+            && let Some((ref starts_at, ref ends_at)) = event_times
+        {
+            diesel::insert_into(events::table)
+                .values(NewEvent {
+                    post_id,
+                    starts_at,
+                    ends_at: ends_at.as_deref(),
+                    location: event.location.as_deref(),
+                    url: event.url.as_deref(),
+                })
+                .execute(conn)?;
+        }
+
+        if let Some(ref review) = form.review {
+            diesel::insert_into(reviews::table)
+                .values(NewReview {
+                    post_id,
+                    item_name: review.item.name.as_deref(),
+                    item_url: review.item.url.as_deref(),
+                    rating: review.rating,
+                    best: review.best,
+                    worst: review.worst,
+                })
+                .execute(conn)?;
+        }
+
+        for (property, place) in [("checkin", &form.checkin), ("location", &form.location)] {
+            if let Some(place) = place {
+                diesel::insert_into(locations::table)
+                    .values(NewLocation {
+                        post_id,
+                        property,
+                        name: place.name.as_deref(),
+                        url: place.url.as_deref(),
+                        latitude: place.latitude,
+                        longitude: place.longitude,
+                    })
+                    .execute(conn)?;
+            }
+        }
+
+        for object in &form.objects {
+            diesel::insert_into(post_objects::table)
+                .values(NewPostObject {
+                    post_id,
+                    property: &object.property,
+                    object: &object.value.to_string(),
+                })
+                .execute(conn)?;
+        }
 
-    Ok(slug)
+        Ok(slug)
+    })
+}
+
+/// The first of `slug`, `slug-2`, `slug-3`, ... that isn't taken. Deleted posts keep their slugs
+/// so that they continue to respond with 410 Gone.
+fn unique_slug(conn: &mut SqliteConnection, slug: &str) -> Result<String, diesel::result::Error> {
+    let mut candidate = slug.to_string();
+    let mut suffix = 1;
+    loop {
+        let existing: i64 = Post::by_slug_including_deleted(&candidate)
+            .count()
+            .get_result(conn)?;
+        if existing == 0 {
+            return Ok(candidate);
+        }
+        suffix += 1;
+        candidate = format!("{}-{}", slug, suffix);
+    }
 }
 
+/// Verifies a token with the configured token or introspection endpoint, or against the tokens
+/// issued by the built in IndieAuth server if it is enabled. Results from the endpoints are kept
+/// in `token_cache`, see `token_cache_max_ttl` in MicropubConfig.
 async fn verify_auth(
     http_client: reqwest::Client,
     site_config: Arc<crate::MicropubSiteConfig>,
+    db: &MicropubDB,
+    token_cache: &TokenCache,
     auth: &str,
-) -> Result<TokenValidateResponse, StatusCode> {
+) -> Result<TokenValidateResponse, MicropubError> {
+    if site_config.micropub.indieauth.is_some() {
+        let mut conn = db.dbconn()?;
+        let validate_response = indieauth::validate_token(&mut conn, &site_config.micropub, auth)?
+            .ok_or_else(|| {
+                warn!("access token is unknown or has been revoked");
+                MicropubError::forbidden("the access token could not be verified")
+            })?;
+        info!("validate_resp (local): {:?}", validate_response);
+        return Ok(validate_response);
+    }
+
+    let max_ttl = Duration::from_secs(site_config.micropub.token_cache_max_ttl);
+    let token_hash = indieauth::token_hash(auth);
+    if let Some(cached) = token_cache.get(&token_hash, Instant::now()) {
+        info!("using cached token verification result");
+        return cached.ok_or_else(|| MicropubError::forbidden("the access token could not be verified"));
+    }
+
+    let result = match site_config.micropub.token_verification {
+        TokenVerification::Legacy => verify_with_token_endpoint(&http_client, &site_config.micropub, auth).await,
+        TokenVerification::Introspection => {
+            introspect_token(&http_client, &site_config.micropub, token_cache, auth).await
+        },
+    };
+    // Errors other than a rejected token (e.g. the endpoint being unreachable) aren't cached
+    match &result {
+        Ok(validate_response) => token_cache.insert_valid(
+            &token_hash,
+            validate_response,
+            max_ttl,
+            Instant::now(),
+            chrono::Utc::now().timestamp(),
+        ),
+        Err(MicropubError::Forbidden(_)) => token_cache.insert_rejected(&token_hash, max_ttl, Instant::now()),
+        Err(_) => (),
+    }
+
+    result
+}
 
+/// Verifies a token with the legacy token endpoint (a GET with the token in the Authorization
+/// header).
+async fn verify_with_token_endpoint(
+    http_client: &reqwest::Client,
+    config: &crate::MicropubConfig,
+    auth: &str,
+) -> Result<TokenValidateResponse, MicropubError> {
     let r = http_client
-        .get(&site_config.micropub.auth_token_endpoint)
+        .get(&config.auth_token_endpoint)
         .header("accept", "application/json")
         .header("Authorization", auth)
         .send()
-        .await;
-
-    let validate_response: TokenValidateResponse = r
+        .await
         .map_err(|e| {
             error!("{:?}", e);
-            HTTPClientError
-        })?
+            MicropubError::internal("error contacting token endpoint")
+        })?;
+
+    // Only these mean the token was rejected, other errors (e.g. a 503 while the endpoint is down)
+    // aren't the token's fault
+    match r.status() {
+        s if s.is_success() => (),
+        reqwest::StatusCode::BAD_REQUEST | reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+            warn!("token endpoint rejected token with status {:?}", r.status());
+            return Err(MicropubError::forbidden("the access token could not be verified"));
+        },
+        s => {
+            error!("token endpoint responded with status {:?}", s);
+            return Err(MicropubError::internal("error verifying access token"));
+        },
+    }
+
+    let validate_response: TokenValidateResponse = r
         .json()
         .await
         .map_err(|e| {
             error!("{:?}", e);
-            ValidateResponseDeserializeError
+            MicropubError::internal("error reading token endpoint response")
         })?;
 
     info!(
@@ -1196,26 +3043,168 @@ async fn verify_auth(
     Ok(validate_response)
 }
 
+/// Verifies a token with an RFC 7662 introspection endpoint (POST `token=...`), as described in
+/// https://indieauth.spec.indieweb.org/#access-token-verification
+async fn introspect_token(
+    http_client: &reqwest::Client,
+    config: &crate::MicropubConfig,
+    token_cache: &TokenCache,
+    auth: &str,
+) -> Result<TokenValidateResponse, MicropubError> {
+    let token = auth
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| MicropubError::unauthorized("malformed authorization header"))?;
+    let endpoint = introspection_endpoint(http_client, config, token_cache).await?;
+
+    let mut request = http_client
+        .post(&endpoint)
+        .header("accept", "application/json")
+        .form(&[("token", token.trim())]);
+    if let Some(authorization) = &config.introspection_authorization {
+        request = request.header("Authorization", authorization);
+    }
+    let r = request
+        .send()
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            MicropubError::internal("error contacting introspection endpoint")
+        })?;
+
+    // The endpoint answers with active: false for bad tokens, an error status means it didn't
+    // accept our request
+    if !r.status().is_success() {
+        error!("introspection endpoint responded with status {:?}", r.status());
+        return Err(MicropubError::internal("error verifying access token"));
+    }
+
+    let introspection_response: IntrospectionResponse = r
+        .json()
+        .await
+        .map_err(|e| {
+            error!("{:?}", e);
+            MicropubError::internal("error reading introspection endpoint response")
+        })?;
+    info!("introspection_resp: {:?}", introspection_response);
+
+    introspection_response
+        .into_validate_response(chrono::Utc::now().timestamp())
+        .ok_or_else(|| {
+            warn!("introspection endpoint reports token is not active");
+            MicropubError::forbidden("the access token could not be verified")
+        })
+}
+
+/// The configured introspection_endpoint, or the one advertised by host_website. A discovered
+/// endpoint is kept in the token cache for a while, then discovered again in case it has changed.
+async fn introspection_endpoint(
+    http_client: &reqwest::Client,
+    config: &crate::MicropubConfig,
+    token_cache: &TokenCache,
+) -> Result<String, MicropubError> {
+    if let Some(endpoint) = &config.introspection_endpoint {
+        return Ok(endpoint.clone());
+    }
+    if let Some(endpoint) = token_cache.introspection_endpoint(Instant::now()) {
+        return Ok(endpoint);
+    }
+
+    let endpoint = discover_introspection_endpoint(http_client, &config.host_website)
+        .await
+        .map_err(|e| {
+            error!("introspection endpoint discovery failed: {:?}", e);
+            MicropubError::internal("could not discover the introspection endpoint")
+        })?;
+    info!("discovered introspection endpoint {:?}", endpoint);
+    token_cache.insert_introspection_endpoint(&endpoint, Instant::now());
+    Ok(endpoint)
+}
+
+/// Follows the indieauth-metadata link (a `Link` header or `<link>` element) of a site to its
+/// IndieAuth server metadata and returns the introspection_endpoint listed there.
+async fn discover_introspection_endpoint(
+    http_client: &reqwest::Client,
+    site: &str,
+) -> Result<String, anyhow::Error> {
+    let r = http_client.get(site).send().await?.error_for_status()?;
+    let page_url = r.url().clone();
+    let header_link = r
+        .headers()
+        .get_all("link")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find_map(metadata_link_from_header);
+    let metadata_link = match header_link {
+        Some(link) => link,
+        None => metadata_link_from_html(&r.text().await?)
+            .ok_or_else(|| anyhow::anyhow!("no indieauth-metadata link on {}", site))?,
+    };
+
+    let metadata: serde_json::Value = http_client
+        .get(page_url.join(&metadata_link)?)
+        .header("accept", "application/json")
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    metadata["introspection_endpoint"]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| anyhow::anyhow!("no introspection_endpoint in metadata for {}", site))
+}
+
 #[cfg(test)]
 mod test {
-    use super::{Photo, MicropubForm};
+    use super::{
+        action_from_form_bytes, allowed_value, escape_like, extra_properties_json, introspect_token,
+        multipart_from_request, photos_from_values, read_multipart, read_multipart_access_token,
+        request_auth, take_access_token, update_extra_properties, verify_auth, EventProperties,
+        MicropubForm, NestedObject, Photo, Place, ReviewItem, ReviewProperties, POST_STATUSES,
+        VISIBILITIES,
+    };
     use crate::models::Post;
 
+    async fn multipart_body(body: &'static str) -> axum::extract::Multipart {
+        let request = http::Request::builder()
+            .header(http::header::CONTENT_TYPE, "multipart/form-data; boundary=BOUNDARY")
+            .body(axum::body::Body::from(body))
+            .unwrap();
+        multipart_from_request(request).await.unwrap()
+    }
+
+    /// A published, public h-entry for tests to override fields of.
+    fn post() -> Post {
+        Post {
+            id: 3,
+            slug: "slug".into(),
+            entry_type: "entry".into(),
+            name: None,
+            content: Some("test content".into()),
+            client_id: None,
+            created_at: "2020-04-04 15:30:00".into(),
+            updated_at: "2022-04-08 19:30:00".into(),
+            content_type: None,
+            bookmark_of: None,
+            in_reply_to: None,
+            like_of: None,
+            repost_of: None,
+            deleted_at: None,
+            summary: None,
+            extra_properties: None,
+            post_status: "published".into(),
+            visibility: "public".into(),
+        }
+    }
+
     #[test]
     fn micropub_form_decode_category_as_array() {
         let qs = b"h=entry&content=this+is+only+a+test+of+micropub&category%5B%5D=test&category%5B%5D=micropub";
         let form = MicropubForm {
-            access_token: None,
-            name: None,
             h: "entry".into(),
             content: "this is only a test of micropub".into(),
-            content_type: None,
             category: vec!["test".into(), "micropub".into()],
-            created_at: None,
-            updated_at: None,
-            slug: None,
-            bookmark_of: None,
-            photos: None,
+            ..Default::default()
         };
 
         assert_eq!(form, MicropubForm::from_form_bytes(&qs[..]).unwrap());
@@ -1225,17 +3214,10 @@ mod test {
     fn micropub_form_decode_category_as_single_param_into_vec() {
         let qs = b"h=entry&content=this+is+only+a+test+of+micropub&category=micropub";
         let form = MicropubForm {
-            access_token: None,
-            name: None,
             h: "entry".into(),
             content: "this is only a test of micropub".into(),
-            content_type: None,
             category: vec!["micropub".into()],
-            created_at: None,
-            updated_at: None,
-            slug: None,
-            bookmark_of: None,
-            photos: None,
+            ..Default::default()
         };
 
         assert_eq!(form, MicropubForm::from_form_bytes(&qs[..]).unwrap());
@@ -1245,67 +3227,187 @@ mod test {
     fn micropub_form_decode_category_missing_empty_vec() {
         let qs = b"h=entry&content=this+is+only+a+test+of+micropub";
         let form = MicropubForm {
-            access_token: None,
-            name: None,
             h: "entry".into(),
             content: "this is only a test of micropub".into(),
-            content_type: None,
-            category: vec![],
-            created_at: None,
-            updated_at: None,
-            slug: None,
-            bookmark_of: None,
-            photos: None,
+            ..Default::default()
         };
 
         assert_eq!(form, MicropubForm::from_form_bytes(&qs[..]).unwrap());
     }
 
+    #[test]
+    fn micropub_form_decode_delete_action() {
+        let qs = b"action=delete&url=https%3A%2F%2Fdavidwilemski.com%2F2020%2F10%2F24%2Ftesting";
+        assert_eq!(
+            Some(("delete".into(), Some("https://davidwilemski.com/2020/10/24/testing".into()))),
+            action_from_form_bytes(&qs[..])
+        );
+    }
+
+    #[test]
+    fn micropub_form_decode_create_has_no_action() {
+        let qs = b"h=entry&content=this+is+only+a+test+of+micropub";
+        assert_eq!(None, action_from_form_bytes(&qs[..]));
+    }
+
+    #[test]
+    fn escape_like_escapes_wildcards() {
+        assert_eq!(escape_like("rust"), "rust");
+        assert_eq!(escape_like("100%_done\\"), "100\\%\\_done\\\\");
+    }
+
     #[test]
     fn micropub_form_decode_content_html() {
         let qs = b"h=entry&name=Test%20Article%20from%20Micropublish.net&content[html]=%3Cdiv%3EThis%20is%20a%20test%20article%3Cbr%3E%3Cbr%3E%3Cstrong%3EIt%20has%20formatting%3Cbr%3E%3Cbr%3E%3C%2Fstrong%3EIt%20can%20%3Ca%20href%3D%22https%3A%2F%2Fdavidwilemski.com%22%3Eembed%20links%3C%2Fa%3E%3C%2Fdiv%3E&category=test&post-status=published&mp-slug=test-article-micropublish-net";
         let form = MicropubForm {
-            access_token: None,
             name: Some("Test Article from Micropublish.net".into()),
             h: "entry".into(),
             content: "<div>This is a test article<br><br><strong>It has formatting<br><br></strong>It can <a href=\"https://davidwilemski.com\">embed links</a></div>".into(),
             content_type: Some("html".into()),
             category: vec!["test".into()],
-            created_at: None,
-            updated_at: None,
-            slug: None,
-            bookmark_of: None,
-            photos: None,
+            slug: Some("test-article-micropublish-net".into()),
+            post_status: Some("published".into()),
+            ..Default::default()
         };
 
         assert_eq!(form, MicropubForm::from_form_bytes(&qs[..]).unwrap());
     }
 
-    // #[test]
-    // fn micropub_json_decode_food_entry() {
-    //     b"{\"type\":[\"h-entry\"],\"properties\":{\"published\":[\"2020-10-03T14:10:06-05:00\"],\"created\":[\"2020-10-03T14:10:06-05:00\"],\"summary\":[\"Just drank: Earl Grey Tea\"],\"drank\":[{\"type\":[\"h-food\"],\"properties\":{\"name\":\"Earl Grey Tea\"}}]}}"
-    // }
+    #[test]
+    fn micropub_form_decode_syndicate_to() {
+        let qs = b"h=entry&content=syndicate+me&mp-syndicate-to%5B%5D=https%3A%2F%2Fmastodon.social%2F%40example&mp-syndicate-to%5B%5D=https%3A%2F%2Fexample.com%2Fother";
+        let form = MicropubForm::from_form_bytes(&qs[..]).unwrap();
+
+        assert_eq!(
+            form.syndicate_to,
+            vec!["https://mastodon.social/@example".to_string(), "https://example.com/other".to_string()]
+        );
+    }
+
+    #[test]
+    fn micropub_json_decode_syndicate_to() {
+        let bytes = b"{\"type\":[\"h-entry\"],\"properties\":{\"content\":[\"syndicate me\"],\"mp-syndicate-to\":[\"https://mastodon.social/@example\"]}}";
+        let form = MicropubForm::from_json_bytes(&bytes[..]).unwrap();
+
+        assert_eq!(form.syndicate_to, vec!["https://mastodon.social/@example".to_string()]);
+    }
+
+    #[test]
+    fn micropub_decode_post_status() {
+        let bytes = b"{\"type\":[\"h-entry\"],\"properties\":{\"content\":[\"not yet\"],\"post-status\":[\"draft\"]}}";
+        let form = MicropubForm::from_json_bytes(&bytes[..]).unwrap();
+        assert_eq!(form.post_status.as_deref(), Some("draft"));
+        assert!(form.extra_properties.is_empty());
+
+        let qs = b"h=entry&content=not+yet&post-status=hidden";
+        assert!(MicropubForm::from_form_bytes(&qs[..]).is_err());
+    }
+
+    #[test]
+    fn micropub_decode_visibility() {
+        let qs = b"h=entry&content=just+for+friends&visibility=unlisted";
+        let form = MicropubForm::from_form_bytes(&qs[..]).unwrap();
+        assert_eq!(form.visibility.as_deref(), Some("unlisted"));
+
+        let bytes = b"{\"type\":[\"h-entry\"],\"properties\":{\"content\":[\"hi\"],\"visibility\":[\"secret\"]}}";
+        assert!(MicropubForm::from_json_bytes(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn micropub_update_allowed_value() {
+        assert_eq!(allowed_value("visibility", &[json!("private")], VISIBILITIES).unwrap(), "private");
+        assert!(allowed_value("visibility", &[json!("secret")], VISIBILITIES).is_err());
+        assert!(allowed_value("post-status", &[], POST_STATUSES).is_err());
+    }
+
+    #[test]
+    fn micropub_json_decode_food_entry() {
+        let bytes = b"{\"type\":[\"h-entry\"],\"properties\":{\"published\":[\"2020-10-03T14:10:06-05:00\"],\"created\":[\"2020-10-03T14:10:06-05:00\"],\"summary\":[\"Just drank: Earl Grey Tea\"],\"drank\":[{\"type\":[\"h-food\"],\"properties\":{\"name\":\"Earl Grey Tea\"}}]}}";
+        let form = MicropubForm {
+            h: "entry".into(),
+            created_at: Some("2020-10-03T14:10:06-05:00".into()),
+            summary: Some("Just drank: Earl Grey Tea".into()),
+            extra_properties: json!({"created": ["2020-10-03T14:10:06-05:00"]})
+                .as_object()
+                .unwrap()
+                .clone(),
+            objects: vec![NestedObject {
+                property: "drank".into(),
+                value: json!({"type": ["h-food"], "properties": {"name": "Earl Grey Tea"}}),
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(form, MicropubForm::from_json_bytes(&bytes[..]).unwrap());
+    }
+
+    #[test]
+    fn micropub_encode_food_entry_to_properties() {
+        let bytes = b"{\"type\":[\"h-entry\"],\"properties\":{\"summary\":[\"Just ate: toast\"],\"ate\":[{\"type\":[\"h-food\"],\"properties\":{\"name\":[\"toast\"],\"nutrition\":{\"calories\":[\"80\"]}}}]}}";
+        let form = MicropubForm::from_json_bytes(&bytes[..]).unwrap();
+
+        assert_eq!(
+            form.to_properties(&["ate".into()]),
+            json!({"properties": {
+                "ate": [{"type": ["h-food"], "properties": {"name": ["toast"], "nutrition": {"calories": ["80"]}}}],
+            }})
+        );
+    }
+
+    #[test]
+    fn micropub_json_decode_review() {
+        let bytes = b"{\"type\":[\"h-review\"],\"properties\":{\"item\":[{\"type\":[\"h-product\"],\"properties\":{\"name\":[\"Something something something tea\"],\"url\":[\"\"]}}],\"rating\":[3],\"content\":[{\"html\":\"test review\"}],\"summary\":[\"it's ok\"]}}";
+        let form = MicropubForm {
+            h: "review".into(),
+            content: "test review".into(),
+            content_type: Some("html".into()),
+            summary: Some("it's ok".into()),
+            review: Some(ReviewProperties {
+                item: ReviewItem {
+                    name: Some("Something something something tea".into()),
+                    url: None,
+                },
+                rating: Some(3.0),
+                best: None,
+                worst: None,
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(form, MicropubForm::from_json_bytes(&bytes[..]).unwrap());
+    }
+
+    #[test]
+    fn micropub_json_decode_review_rejects_rating_out_of_range() {
+        let bytes = b"{\"type\":[\"h-review\"],\"properties\":{\"item\":[\"https://example.com/tea\"],\"rating\":[\"7\"],\"best\":[\"10\"],\"worst\":[\"8\"]}}";
+        assert!(MicropubForm::from_json_bytes(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn micropub_encode_review_to_properties() {
+        let bytes = b"{\"type\":[\"h-review\"],\"properties\":{\"item\":[\"https://example.com/tea\"],\"rating\":[4],\"content\":[\"good\"]}}";
+        let form = MicropubForm::from_json_bytes(&bytes[..]).unwrap();
 
-    // #[test]
-    // fn micropub_json_decode_review() {
-    //     b"{\"type\":[\"h-review\"],\"properties\":{\"item\":[{\"type\":[\"h-product\"],\"properties\":{\"name\":[\"Something something something tea\"],\"url\":[\"\"]}}],\"rating\":[3],\"content\":[{\"html\":\"test review\"}],\"summary\":[\"it's ok\"]}}";
-    // }
+        assert_eq!(
+            form.to_properties(&["item".into(), "rating".into()]),
+            json!({"properties": {
+                "item": [{"type": ["h-product"], "properties": {"url": ["https://example.com/tea"]}}],
+                "rating": [4.0],
+            }})
+        );
+    }
 
     #[test]
     fn micropub_json_decode_post_entry_from_quill() {
         let bytes = b"{\"type\":[\"h-entry\"],\"properties\":{\"name\":[\"Testing quill\"],\"content\":[{\"html\":\"<p>This is a test of https:\\/\\/quill.p3k.io<\\/p>\\n<p>\\n  hello hello\\n  <br \\/>\\n<\\/p>\"}],\"category\":[\"test\"],\"mp-slug\":[\"quill-test\"]}}";
         let form = MicropubForm {
-            access_token: None,
             name: Some("Testing quill".into()),
             h: "entry".into(),
             content: "<p>This is a test of https://quill.p3k.io</p>\n<p>\n  hello hello\n  <br />\n</p>".into(),
             content_type: Some("html".into()),
             category: vec!["test".into()],
-            created_at: None,
-            updated_at: None,
             slug: Some("quill-test".into()),
-            bookmark_of: None,
-            photos: None,
+            ..Default::default()
         };
 
         assert_eq!(form, MicropubForm::from_json_bytes(&bytes[..]).unwrap());
@@ -1315,17 +3417,11 @@ mod test {
     fn micropub_json_decode_bookmark_of_entry() {
         let bytes = b"{\"type\":[\"h-entry\"],\"properties\":{\"name\":[\"Testing bookmarks\"],\"content\":[\"Bookmark test\"],\"bookmark-of\":[\"https://davidwilemski.com\"]}}";
         let form = MicropubForm {
-            access_token: None,
             name: Some("Testing bookmarks".into()),
             h: "entry".into(),
             content: "Bookmark test".into(),
-            content_type: None,
-            category: vec![],
-            created_at: None,
-            updated_at: None,
-            slug: None,
             bookmark_of: Some("https://davidwilemski.com".into()),
-            photos: None,
+            ..Default::default()
         };
 
         assert_eq!(form, MicropubForm::from_json_bytes(&bytes[..]).unwrap());
@@ -1335,17 +3431,13 @@ mod test {
     fn micropub_json_decode_post_entry_markdown_format() {
         let bytes = b"{\"type\":[\"h-entry\"],\"properties\":{\"name\":[\"Testing markdown\"],\"content\":[{\"markdown\":\"This _is_ a *markdown* document. \\n # Header 1 \\n normal text\"}],\"category\":[\"markdown\"],\"mp-slug\":[\"markdown-test\"]}}";
         let form = MicropubForm {
-            access_token: None,
             name: Some("Testing markdown".into()),
             h: "entry".into(),
             content: "This _is_ a *markdown* document. \n # Header 1 \n normal text".into(),
             content_type: Some("markdown".into()),
             category: vec!["markdown".into()],
-            created_at: None,
-            updated_at: None,
             slug: Some("markdown-test".into()),
-            bookmark_of: None,
-            photos: None,
+            ..Default::default()
         };
 
         assert_eq!(form, MicropubForm::from_json_bytes(&bytes[..]).unwrap());
@@ -1355,17 +3447,14 @@ mod test {
     fn micropub_json_decode_handles_published_property() {
         let bytes = b"{\"type\":[\"h-entry\"],\"properties\":{\"name\":[\"Testing published\"],\"content\":[{\"html\":\"content!\"}],\"category\":[\"publish-date\"],\"mp-slug\":[\"publish-date-slug\"], \"published\":[\"2020-04-04 15:30:00\"]}}";
         let form = MicropubForm {
-            access_token: None,
             name: Some("Testing published".into()),
             h: "entry".into(),
             content: "content!".into(),
             content_type: Some("html".into()),
             category: vec!["publish-date".into()],
             created_at: Some("2020-04-04 15:30:00".into()),
-            updated_at: None,
             slug: Some("publish-date-slug".into()),
-            bookmark_of: None,
-            photos: None,
+            ..Default::default()
         };
 
         assert_eq!(form, MicropubForm::from_json_bytes(&bytes[..]).unwrap());
@@ -1375,22 +3464,15 @@ mod test {
     fn micropub_form_decode_photo_property() {
         let bytes = b"{\"type\":[\"h-entry\"],\"properties\":{\"content\":[\"test upload\"],\"photo\":[{\"value\":\"https:\\/\\/davidwilemski.com\\/media\\/2a2ae02f9addf60f708298221e661db15b8afc340d8b934bc94b9e37f293074f\",\"alt\":\"test upload\"}]}}";
         let form = MicropubForm {
-            access_token: None,
-            name: None,
             h: "entry".into(),
             content: "test upload".into(),
-            content_type: None,
-            category: vec![],
-            created_at: None,
-            updated_at: None,
-            slug: None,
-            bookmark_of: None,
             photos: Some(vec![
                 Photo {
                     url: "https://davidwilemski.com/media/2a2ae02f9addf60f708298221e661db15b8afc340d8b934bc94b9e37f293074f".into(),
                     alt: Some("test upload".into()),
                 }
             ]),
+            ..Default::default()
         };
 
         assert_eq!(form, MicropubForm::from_json_bytes(&bytes[..]).unwrap());
@@ -1400,16 +3482,8 @@ mod test {
     fn micropub_form_decode_multiple_photo_property() {
         let bytes = b"{\"type\":[\"h-entry\"],\"properties\":{\"content\":[\"test upload\"],\"photo\":[{\"value\":\"https:\\/\\/davidwilemski.com\\/media\\/2a2ae02f9addf60f708298221e661db15b8afc340d8b934bc94b9e37f293074f\",\"alt\":\"test upload\"},\"https:\\/\\/davidwilemski.com\\/media\\/df1dfea9b0a062e8e27ee6fed1df597995547e16a73570107ff475b33d59f4fb\"]}}";
         let form = MicropubForm {
-            access_token: None,
-            name: None,
             h: "entry".into(),
             content: "test upload".into(),
-            content_type: None,
-            category: vec![],
-            created_at: None,
-            updated_at: None,
-            slug: None,
-            bookmark_of: None,
             photos: Some(vec![
                 Photo {
                     url: "https://davidwilemski.com/media/2a2ae02f9addf60f708298221e661db15b8afc340d8b934bc94b9e37f293074f".into(),
@@ -1420,30 +3494,241 @@ mod test {
                     alt: None,
                 }
             ]),
+            ..Default::default()
         };
 
         assert_eq!(form, MicropubForm::from_json_bytes(&bytes[..]).unwrap());
     }
 
+    #[test]
+    fn micropub_form_decode_handles_published_property() {
+        let qs = b"h=entry&name=Testing+published&content=content%21&category=publish-date&mp-slug=publish-date-slug&published=2020-04-04+15%3A30%3A00&summary=a+summary";
+        let form = MicropubForm {
+            name: Some("Testing published".into()),
+            h: "entry".into(),
+            content: "content!".into(),
+            category: vec!["publish-date".into()],
+            created_at: Some("2020-04-04 15:30:00".into()),
+            slug: Some("publish-date-slug".into()),
+            summary: Some("a summary".into()),
+            ..Default::default()
+        };
+
+        assert_eq!(form, MicropubForm::from_form_bytes(&qs[..]).unwrap());
+    }
+
+    #[test]
+    fn micropub_form_decode_urlencoded_photo_property() {
+        let qs = b"h=entry&content=test+upload&photo=https%3A%2F%2Fdavidwilemski.com%2Fmedia%2F2a2ae02f9addf60f708298221e661db15b8afc340d8b934bc94b9e37f293074f";
+        let form = MicropubForm::from_form_bytes(&qs[..]).unwrap();
+
+        assert_eq!(
+            form.photos,
+            Some(vec![
+                Photo {
+                    url: "https://davidwilemski.com/media/2a2ae02f9addf60f708298221e661db15b8afc340d8b934bc94b9e37f293074f".into(),
+                    alt: None,
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn micropub_form_decode_urlencoded_multiple_photo_property() {
+        let qs = b"h=entry&content=test+upload&photo%5B%5D%5Bvalue%5D=https%3A%2F%2Fdavidwilemski.com%2Fmedia%2F2a2ae02f9addf60f708298221e661db15b8afc340d8b934bc94b9e37f293074f&photo%5B%5D%5Balt%5D=test+upload&photo%5B%5D=https%3A%2F%2Fdavidwilemski.com%2Fmedia%2Fdf1dfea9b0a062e8e27ee6fed1df597995547e16a73570107ff475b33d59f4fb";
+        let form = MicropubForm::from_form_bytes(&qs[..]).unwrap();
+
+        assert_eq!(
+            form.photos,
+            Some(vec![
+                Photo {
+                    url: "https://davidwilemski.com/media/2a2ae02f9addf60f708298221e661db15b8afc340d8b934bc94b9e37f293074f".into(),
+                    alt: Some("test upload".into()),
+                },
+                Photo {
+                    url: "https://davidwilemski.com/media/df1dfea9b0a062e8e27ee6fed1df597995547e16a73570107ff475b33d59f4fb".into(),
+                    alt: None,
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn micropub_form_decode_photo_alt_without_photo_is_ignored() {
+        let qs = b"h=entry&content=test&photo%5Balt%5D=orphaned";
+        let form = MicropubForm::from_form_bytes(&qs[..]).unwrap();
+
+        assert_eq!(form.photos, None);
+    }
+
+    #[test]
+    fn micropub_json_decode_response_properties() {
+        let bytes = b"{\"type\":[\"h-entry\"],\"properties\":{\"content\":[\"nice post\"],\"in-reply-to\":[\"https://example.com/a\"],\"like-of\":[\"https://example.com/b\"],\"repost-of\":[{\"type\":[\"h-cite\"],\"properties\":{\"url\":[\"https://example.com/c\"],\"name\":[\"C\"]}}]}}";
+        let form = MicropubForm::from_json_bytes(&bytes[..]).unwrap();
+
+        assert_eq!(form.in_reply_to.as_deref(), Some("https://example.com/a"));
+        assert_eq!(form.like_of.as_deref(), Some("https://example.com/b"));
+        assert_eq!(form.repost_of.as_deref(), Some("https://example.com/c"));
+    }
+
+    #[test]
+    fn micropub_form_decode_response_properties() {
+        let qs = b"h=entry&content=nice+post&in-reply-to=https%3A%2F%2Fexample.com%2Fa&like-of=https%3A%2F%2Fexample.com%2Fb&repost-of=https%3A%2F%2Fexample.com%2Fc";
+        let form = MicropubForm::from_form_bytes(&qs[..]).unwrap();
+
+        assert_eq!(form.in_reply_to.as_deref(), Some("https://example.com/a"));
+        assert_eq!(form.like_of.as_deref(), Some("https://example.com/b"));
+        assert_eq!(form.repost_of.as_deref(), Some("https://example.com/c"));
+    }
+
+    #[test]
+    fn micropub_json_decode_event() {
+        let bytes = b"{\"type\":[\"h-event\"],\"properties\":{\"name\":[\"IndieWebCamp\"],\"start\":[\"2026-11-01T10:00:00-05:00\"],\"end\":[\"2026-11-01T17:00:00-05:00\"],\"location\":[{\"type\":[\"h-card\"],\"properties\":{\"name\":[\"Public Library\"],\"locality\":[\"Ann Arbor\"]}}],\"url\":[\"https://example.com/camp\"]}}";
+        let form = MicropubForm::from_json_bytes(&bytes[..]).unwrap();
+
+        assert_eq!(form.h, "event");
+        assert_eq!(form.content, "");
+        assert_eq!(
+            form.event,
+            Some(EventProperties {
+                start: "2026-11-01T10:00:00-05:00".into(),
+                end: Some("2026-11-01T17:00:00-05:00".into()),
+                location: Some("Public Library".into()),
+                url: Some("https://example.com/camp".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn micropub_form_decode_event() {
+        let qs = b"h=event&name=Meetup&start=2026-11-01+18%3A00%3A00&location=Downtown";
+        let form = MicropubForm::from_form_bytes(&qs[..]).unwrap();
+
+        assert_eq!(
+            form.event,
+            Some(EventProperties {
+                start: "2026-11-01 18:00:00".into(),
+                end: None,
+                location: Some("Downtown".into()),
+                url: None,
+            })
+        );
+    }
+
+    #[test]
+    fn micropub_form_decode_event_requires_start() {
+        let qs = b"h=event&name=Meetup";
+        assert!(MicropubForm::from_form_bytes(&qs[..]).is_err());
+    }
+
+    #[test]
+    fn micropub_json_decode_rejects_several_single_values() {
+        let bytes = br#"{"type":["h-event"],"properties":{"name":["Camp"],"start":["2026-11-01T10:00:00-05:00","2026-11-02T10:00:00-05:00"]}}"#;
+        assert!(MicropubForm::from_json_bytes(&bytes[..]).is_err());
+
+        let bytes = br#"{"type":["h-entry"],"properties":{"content":["hi"],"in-reply-to":["https://example.com/a","https://example.com/b"]}}"#;
+        assert!(MicropubForm::from_json_bytes(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn micropub_encode_event_to_properties() {
+        let mut form = MicropubForm::from_form_bytes(b"h=event&name=Meetup&start=2026-11-01+18%3A00%3A00").unwrap();
+        form.slug = Some("2026/10/16/meetup".into());
+        form.event.as_mut().unwrap().url = Some("https://example.com/meetup".into());
+
+        let properties = form.to_properties(&[]);
+        assert_eq!(properties["type"], json!(["h-event"]));
+        assert_eq!(properties["properties"]["start"], json!(["2026-11-01 18:00:00"]));
+        assert_eq!(properties["properties"]["url"], json!(["https://example.com/meetup"]));
+        assert!(properties["properties"].get("end").is_none());
+    }
+
+    #[test]
+    fn micropub_json_decode_checkin() {
+        let bytes = b"{\"type\":[\"h-entry\"],\"properties\":{\"content\":[\"coffee time\"],\"checkin\":[{\"type\":[\"h-card\"],\"properties\":{\"name\":[\"Blue Bottle\"],\"url\":[\"https://foursquare.com/v/123\"],\"latitude\":[37.7764],\"longitude\":[-122.4232],\"locality\":[\"San Francisco\"]}}],\"location\":[{\"type\":[\"h-adr\"],\"properties\":{\"latitude\":[\"37.7764\"],\"longitude\":[\"-122.4232\"],\"locality\":[\"San Francisco\"]}}]}}";
+        let form = MicropubForm::from_json_bytes(&bytes[..]).unwrap();
+
+        assert_eq!(
+            form.checkin,
+            Some(Place {
+                name: Some("Blue Bottle".into()),
+                url: Some("https://foursquare.com/v/123".into()),
+                latitude: Some(37.7764),
+                longitude: Some(-122.4232),
+            })
+        );
+        assert_eq!(
+            form.location,
+            Some(Place {
+                name: Some("San Francisco".into()),
+                url: None,
+                latitude: Some(37.7764),
+                longitude: Some(-122.4232),
+            })
+        );
+    }
+
+    #[test]
+    fn micropub_form_decode_geo_uri_location() {
+        let qs = b"h=entry&content=here&location=geo%3A37.786971%2C-122.399677%3Bu%3D35";
+        let form = MicropubForm::from_form_bytes(&qs[..]).unwrap();
+
+        assert_eq!(
+            form.location,
+            Some(Place {
+                name: None,
+                url: None,
+                latitude: Some(37.786971),
+                longitude: Some(-122.399677),
+            })
+        );
+        assert_eq!(
+            form.to_properties(&["location".into()]),
+            json!({"properties": {"location": ["geo:37.786971,-122.399677"]}})
+        );
+    }
+
+    #[test]
+    fn micropub_encode_checkin_to_properties() {
+        let bytes = b"{\"type\":[\"h-entry\"],\"properties\":{\"content\":[\"hi\"],\"checkin\":[{\"type\":[\"h-card\"],\"properties\":{\"name\":[\"Blue Bottle\"],\"geo\":[\"geo:37.7764,-122.4232\"]}}]}}";
+        let form = MicropubForm::from_json_bytes(&bytes[..]).unwrap();
+
+        assert_eq!(
+            form.to_properties(&["checkin".into()]),
+            json!({"properties": {"checkin": [{"type": ["h-card"], "properties": {
+                "name": ["Blue Bottle"],
+                "latitude": [37.7764],
+                "longitude": [-122.4232],
+            }}]}})
+        );
+    }
+
+    #[test]
+    fn micropub_update_photo_values() {
+        let values = json!(["https://example.com/1.jpg", {"value": "https://example.com/2.jpg", "alt": "two"}]);
+        assert_eq!(
+            photos_from_values(values.as_array().unwrap()).unwrap(),
+            vec![
+                Photo { url: "https://example.com/1.jpg".into(), alt: None },
+                Photo { url: "https://example.com/2.jpg".into(), alt: Some("two".into()) },
+            ]
+        );
+
+        let bad_values = json!([{"alt": "missing url"}]);
+        assert!(photos_from_values(bad_values.as_array().unwrap()).is_err());
+    }
+
     #[test]
     fn micropub_encode_post_to_properties() {
         let post = Post {
-            id: 3,
-            slug: "slug".into(),
-            entry_type: "entry".into(),
             name: Some("title".into()),
-            content: Some("test content".into()),
-            client_id: None,
-            created_at: "2020-04-04 15:30:00".into(),
-            updated_at: "2022-04-08 19:30:00".into(),
-            content_type: None,
-            bookmark_of: None,
+            ..post()
         };
-        let form = MicropubForm::from_post(&post, &vec![], &vec![]);
-        let json_properties = b"{\"type\":[\"h-entry\"],\"properties\":{\"mp-slug\":[\"slug\"],\"name\":[\"title\"],\"content\":[\"test content\"],\"published\":[\"2020-04-04 15:30:00\"],\"updated\":[\"2022-04-08 19:30:00\"]}}";
+        let form = MicropubForm::from_post(&post, &[], &[]);
+        let json_properties = b"{\"type\":[\"h-entry\"],\"properties\":{\"mp-slug\":[\"slug\"],\"name\":[\"title\"],\"content\":[\"test content\"],\"published\":[\"2020-04-04 15:30:00\"],\"updated\":[\"2022-04-08 19:30:00\"],\"post-status\":[\"published\"],\"visibility\":[\"public\"]}}";
 
         assert_eq!(
-            MicropubForm::from_json_bytes(form.to_properties_json().unwrap().as_bytes()).unwrap(),
+            MicropubForm::from_json_bytes(form.to_properties_json(&[]).unwrap().as_bytes()).unwrap(),
             MicropubForm::from_json_bytes(json_properties).unwrap()
         );
     }
@@ -1451,23 +3736,17 @@ mod test {
     #[test]
     fn micropub_encode_post_to_properties_with_html_content() {
         let post = Post {
-            id: 3,
-            slug: "slug".into(),
-            entry_type: "entry".into(),
             name: Some("title".into()),
             content: Some("<b>test content</b>".into()),
-            client_id: None,
-            created_at: "2020-04-04 15:30:00".into(),
-            updated_at: "2022-04-08 19:30:00".into(),
             content_type: Some("html".into()),
-            bookmark_of: None,
+            ..post()
         };
-        let form = MicropubForm::from_post(&post, &vec![], &vec![]);
+        let form = MicropubForm::from_post(&post, &[], &[]);
         eprintln!("form: {:?}", form);
-        let json_properties = b"{\"type\":[\"h-entry\"],\"properties\":{\"mp-slug\":[\"slug\"],\"name\":[\"title\"],\"content\":[{\"html\":\"<b>test content</b>\"}],\"published\":[\"2020-04-04 15:30:00\"],\"updated\":[\"2022-04-08 19:30:00\"]}}";
+        let json_properties = b"{\"type\":[\"h-entry\"],\"properties\":{\"mp-slug\":[\"slug\"],\"name\":[\"title\"],\"content\":[{\"html\":\"<b>test content</b>\"}],\"published\":[\"2020-04-04 15:30:00\"],\"updated\":[\"2022-04-08 19:30:00\"],\"post-status\":[\"published\"],\"visibility\":[\"public\"]}}";
 
         assert_eq!(
-            MicropubForm::from_json_bytes(form.to_properties_json().unwrap().as_bytes()).unwrap(),
+            MicropubForm::from_json_bytes(form.to_properties_json(&[]).unwrap().as_bytes()).unwrap(),
             MicropubForm::from_json_bytes(json_properties).unwrap()
         );
     }
@@ -1475,22 +3754,13 @@ mod test {
     #[test]
     fn micropub_encode_post_to_properties_without_name() {
         let post = Post {
-            id: 3,
-            slug: "slug".into(),
-            entry_type: "entry".into(),
-            name: None,
-            content: Some("test content".into()),
-            client_id: None,
-            created_at: "2020-04-04 15:30:00".into(),
-            updated_at: "2022-04-08 19:30:00".into(),
-            content_type: None,
-            bookmark_of: None,
+            ..post()
         };
-        let form = MicropubForm::from_post(&post, &vec![], &vec![]);
-        let json_properties = b"{\"type\":[\"h-entry\"],\"properties\":{\"mp-slug\":[\"slug\"],\"content\":[\"test content\"],\"published\":[\"2020-04-04 15:30:00\"],\"updated\":[\"2022-04-08 19:30:00\"]}}";
+        let form = MicropubForm::from_post(&post, &[], &[]);
+        let json_properties = b"{\"type\":[\"h-entry\"],\"properties\":{\"mp-slug\":[\"slug\"],\"content\":[\"test content\"],\"published\":[\"2020-04-04 15:30:00\"],\"updated\":[\"2022-04-08 19:30:00\"],\"post-status\":[\"published\"],\"visibility\":[\"public\"]}}";
 
         assert_eq!(
-            MicropubForm::from_json_bytes(form.to_properties_json().unwrap().as_bytes()).unwrap(),
+            MicropubForm::from_json_bytes(form.to_properties_json(&[]).unwrap().as_bytes()).unwrap(),
             MicropubForm::from_json_bytes(json_properties).unwrap()
         );
     }
@@ -1498,48 +3768,274 @@ mod test {
     #[test]
     fn micropub_encode_post_to_properties_with_categories() {
         let post = Post {
-            id: 3,
-            slug: "slug".into(),
-            entry_type: "entry".into(),
-            name: None,
-            content: Some("test content".into()),
-            client_id: None,
-            created_at: "2020-04-04 15:30:00".into(),
-            updated_at: "2022-04-08 19:30:00".into(),
-            content_type: None,
-            bookmark_of: None,
+            ..post()
         };
         let categories: Vec<String> = vec!["tag1".into(), "tag2".into()];
-        let form = MicropubForm::from_post(&post, &categories, &vec![]);
-        let json_properties = b"{\"type\":[\"h-entry\"],\"properties\":{\"mp-slug\":[\"slug\"],\"content\":[\"test content\"],\"published\":[\"2020-04-04 15:30:00\"],\"updated\":[\"2022-04-08 19:30:00\"],\"category\":[\"tag1\",\"tag2\"]}}";
+        let form = MicropubForm::from_post(&post, &categories, &[]);
+        let json_properties = b"{\"type\":[\"h-entry\"],\"properties\":{\"mp-slug\":[\"slug\"],\"content\":[\"test content\"],\"published\":[\"2020-04-04 15:30:00\"],\"updated\":[\"2022-04-08 19:30:00\"],\"post-status\":[\"published\"],\"visibility\":[\"public\"],\"category\":[\"tag1\",\"tag2\"]}}";
 
         assert_eq!(
-            MicropubForm::from_json_bytes(form.to_properties_json().unwrap().as_bytes()).unwrap(),
+            MicropubForm::from_json_bytes(form.to_properties_json(&[]).unwrap().as_bytes()).unwrap(),
             MicropubForm::from_json_bytes(json_properties).unwrap()
         );
     }
 
+    #[test]
+    fn micropub_encode_post_to_properties_with_reply() {
+        let post = Post {
+            in_reply_to: Some("https://example.com/a".into()),
+            ..post()
+        };
+        let form = MicropubForm::from_post(&post, &[], &[]);
+
+        assert_eq!(
+            form.to_properties(&["in-reply-to".into()]),
+            json!({"properties": {"in-reply-to": ["https://example.com/a"]}})
+        );
+    }
+
+    #[test]
+    fn micropub_json_unknown_properties_round_trip() {
+        let bytes = b"{\"type\":[\"h-entry\"],\"properties\":{\"content\":[\"hi\"],\"mp-slug\":[\"hi\"],\"mp-unknown\":[\"x\"],\"listen-of\":[{\"type\":[\"h-cite\"],\"properties\":{\"name\":[\"A Song\"]}}]}}";
+        let form = MicropubForm::from_json_bytes(&bytes[..]).unwrap();
+
+        assert_eq!(
+            extra_properties_json(&form.extra_properties).as_deref(),
+            Some("{\"listen-of\":[{\"properties\":{\"name\":[\"A Song\"]},\"type\":[\"h-cite\"]}]}")
+        );
+        assert_eq!(
+            form.to_properties(&["listen-of".into(), "mp-unknown".into()]),
+            json!({"properties": {"listen-of": [{"type": ["h-cite"], "properties": {"name": ["A Song"]}}]}})
+        );
+    }
+
+    #[test]
+    fn micropub_entry_url_round_trips_through_source() {
+        let bytes = br#"{"type":["h-entry"],"properties":{"content":["hi"],"url":["https://example.com/elsewhere"],"rating":["5"]}}"#;
+        let form = MicropubForm::from_json_bytes(&bytes[..]).unwrap();
+        let post = Post {
+            entry_type: form.h.clone(),
+            content: Some(form.content.clone()),
+            extra_properties: extra_properties_json(&form.extra_properties),
+            ..post()
+        };
+
+        let source = MicropubForm::from_post(&post, &[], &[]).to_properties(&[]);
+        assert_eq!(source["properties"]["url"], json!(["https://example.com/elsewhere"]));
+        assert_eq!(source["properties"]["rating"], json!(["5"]));
+    }
+
+    #[test]
+    fn micropub_update_extra_properties() {
+        let mut post = Post {
+            extra_properties: Some("{\"rsvp\":[\"maybe\"],\"syndication\":[\"https://a.example\"]}".into()),
+            ..post()
+        };
+
+        update_extra_properties(&mut post, |extra| {
+            extra.insert("rsvp".into(), json!(["yes"]));
+        });
+        update_extra_properties(&mut post, |extra| {
+            extra.remove("syndication");
+        });
+        assert_eq!(post.extra_properties.as_deref(), Some("{\"rsvp\":[\"yes\"]}"));
+
+        update_extra_properties(&mut post, |extra| {
+            extra.remove("rsvp");
+        });
+        assert_eq!(post.extra_properties, None);
+    }
+
+    #[test]
+    fn micropub_encode_post_to_requested_properties() {
+        let post = Post {
+            name: Some("title".into()),
+            ..post()
+        };
+        let categories: Vec<String> = vec!["tag1".into()];
+        let form = MicropubForm::from_post(&post, &categories, &[]);
+
+        assert_eq!(
+            form.to_properties(&["content".into(), "category".into()]),
+            json!({"properties": {"content": ["test content"], "category": ["tag1"]}})
+        );
+    }
+
     #[test]
     fn micropub_encode_post_to_properties_with_photos() {
         let post = Post {
-            id: 3,
-            slug: "slug".into(),
-            entry_type: "entry".into(),
-            name: None,
-            content: Some("test content".into()),
-            client_id: None,
-            created_at: "2020-04-04 15:30:00".into(),
-            updated_at: "2022-04-08 19:30:00".into(),
-            content_type: None,
-            bookmark_of: None,
+            ..post()
         };
         let photos: Vec<(String, Option<String>)> = vec![("url1".into(), None), ("url2".into(), Some("alt text".into()))];
-        let form = MicropubForm::from_post(&post, &vec![], &photos);
-        let json_properties = b"{\"type\":[\"h-entry\"],\"properties\":{\"mp-slug\":[\"slug\"],\"content\":[\"test content\"],\"published\":[\"2020-04-04 15:30:00\"],\"updated\":[\"2022-04-08 19:30:00\"],\"photo\":[{\"value\":\"url1\"},{\"value\":\"url2\",\"alt\":\"alt text\"}]}}";
+        let form = MicropubForm::from_post(&post, &[], &photos);
+        let json_properties = b"{\"type\":[\"h-entry\"],\"properties\":{\"mp-slug\":[\"slug\"],\"content\":[\"test content\"],\"published\":[\"2020-04-04 15:30:00\"],\"updated\":[\"2022-04-08 19:30:00\"],\"post-status\":[\"published\"],\"visibility\":[\"public\"],\"photo\":[{\"value\":\"url1\"},{\"value\":\"url2\",\"alt\":\"alt text\"}]}}";
 
         assert_eq!(
-            MicropubForm::from_json_bytes(form.to_properties_json().unwrap().as_bytes()).unwrap(),
+            MicropubForm::from_json_bytes(form.to_properties_json(&[]).unwrap().as_bytes()).unwrap(),
             MicropubForm::from_json_bytes(json_properties).unwrap()
         );
     }
+
+    #[tokio::test]
+    async fn multipart_create_splits_fields_and_photos() {
+        let body = concat!(
+            "--BOUNDARY\r\n",
+            "Content-Disposition: form-data; name=\"h\"\r\n\r\n",
+            "entry\r\n",
+            "--BOUNDARY\r\n",
+            "Content-Disposition: form-data; name=\"content\"\r\n\r\n",
+            "a photo\r\n",
+            "--BOUNDARY\r\n",
+            "Content-Disposition: form-data; name=\"photo\"; filename=\"a.jpg\"\r\n",
+            "Content-Type: image/jpeg\r\n\r\n",
+            "not really a jpeg\r\n",
+            "--BOUNDARY--\r\n",
+        );
+        let mut multipart = multipart_body(body).await;
+        let (mut fields, mut files) = (vec![], vec![]);
+        read_multipart(&mut multipart, &mut fields, &mut files).await.unwrap();
+
+        assert_eq!(
+            fields,
+            vec![("h".to_string(), "entry".to_string()), ("content".to_string(), "a photo".to_string())]
+        );
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].filename.as_deref(), Some("a.jpg"));
+        assert_eq!(files[0].content_type.as_deref(), Some("image/jpeg"));
+        assert_eq!(&files[0].contents[..], b"not really a jpeg");
+    }
+
+    #[tokio::test]
+    async fn multipart_access_token_is_read_before_files() {
+        let body = concat!(
+            "--BOUNDARY\r\n",
+            "Content-Disposition: form-data; name=\"h\"\r\n\r\n",
+            "entry\r\n",
+            "--BOUNDARY\r\n",
+            "Content-Disposition: form-data; name=\"access_token\"\r\n\r\n",
+            "abc\r\n",
+            "--BOUNDARY\r\n",
+            "Content-Disposition: form-data; name=\"photo\"; filename=\"a.jpg\"\r\n\r\n",
+            "not really a jpeg\r\n",
+            "--BOUNDARY--\r\n",
+        );
+        let mut multipart = multipart_body(body).await;
+        let mut fields = vec![];
+        assert_eq!(read_multipart_access_token(&mut multipart, &mut fields).await.unwrap(), "abc");
+        assert_eq!(fields, vec![("h".to_string(), "entry".to_string())]);
+        let mut files = vec![];
+        read_multipart(&mut multipart, &mut fields, &mut files).await.unwrap();
+        assert_eq!(files.len(), 1);
+
+        // the token has to come before any files, and only once
+        let body = concat!(
+            "--BOUNDARY\r\n",
+            "Content-Disposition: form-data; name=\"photo\"; filename=\"a.jpg\"\r\n\r\n",
+            "not really a jpeg\r\n",
+            "--BOUNDARY\r\n",
+            "Content-Disposition: form-data; name=\"access_token\"\r\n\r\n",
+            "abc\r\n",
+            "--BOUNDARY--\r\n",
+        );
+        assert!(read_multipart_access_token(&mut multipart_body(body).await, &mut vec![]).await.is_err());
+        assert!(read_multipart(&mut multipart_body(body).await, &mut vec![], &mut vec![]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn introspection_with_discovered_endpoint() {
+        let site = crate::fake_auth::spawn(crate::fake_auth::FakeAuthConfig {
+            scope: "create".into(),
+            inactive_token: "revoked".into(),
+            ..Default::default()
+        }).await.unwrap();
+
+        let config: crate::MicropubConfig = toml::from_str(&format!(r#"
+            host_website = "{site}"
+            media_endpoint = "{site}media"
+            micropub_endpoint = "{site}micropub"
+            current_timezone_offset = 0
+            token_verification = "introspection"
+        "#)).unwrap();
+        let client = reqwest::Client::new();
+        let token_cache = crate::auth::TokenCache::default();
+
+        let validate_response = introspect_token(&client, &config, &token_cache, "Bearer valid").await.unwrap();
+        assert_eq!(validate_response.me, "https://example.com");
+        assert!(validate_response.has_scope("create"));
+        assert_eq!(
+            token_cache.introspection_endpoint(std::time::Instant::now()),
+            Some(format!("{}introspect", site))
+        );
+
+        let e = introspect_token(&client, &config, &token_cache, "Bearer revoked").await.unwrap_err();
+        assert_eq!(e.status_code(), http::StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn token_endpoint_errors_are_not_cached() {
+        let server = crate::fake_auth::spawn(crate::fake_auth::FakeAuthConfig {
+            fail_requests: 1,
+            ..Default::default()
+        }).await.unwrap();
+        let site_config: crate::MicropubSiteConfig = toml::from_str(&format!(r#"
+            blobject_store_base_uri = "http://127.0.0.1:1/"
+            template_dir = "."
+            database_url = ":memory:"
+
+            [micropub]
+            auth_token_endpoint = "{server}token"
+            host_website = "https://example.com/"
+            media_endpoint = "https://example.com/media"
+            micropub_endpoint = "https://example.com/micropub"
+            current_timezone_offset = 0
+
+            [site]
+            site_name = "test"
+            menu_items = []
+            socials = []
+        "#)).unwrap();
+        let site_config = std::sync::Arc::new(site_config);
+        let db = crate::handler::MicropubDB::new(std::sync::Arc::new(crate::new_dbconn_pool(":memory:").unwrap()));
+        let token_cache = crate::auth::TokenCache::default();
+        let client = reqwest::Client::new();
+
+        let e = verify_auth(client.clone(), site_config.clone(), &db, &token_cache, "Bearer abc")
+            .await
+            .unwrap_err();
+        assert_eq!(e.status_code(), http::StatusCode::INTERNAL_SERVER_ERROR);
+
+        let validate_response = verify_auth(client, site_config, &db, &token_cache, "Bearer abc")
+            .await
+            .unwrap();
+        assert_eq!(validate_response.me, "https://example.com");
+    }
+
+    #[test]
+    fn access_token_from_header_or_request() {
+        let mut headers = http::HeaderMap::new();
+        assert_eq!(request_auth(&headers, Some("abc")).unwrap(), "Bearer abc");
+        assert_eq!(
+            request_auth(&headers, None).unwrap_err().status_code(),
+            http::StatusCode::UNAUTHORIZED
+        );
+
+        headers.insert(http::header::AUTHORIZATION, "Bearer xyz".parse().unwrap());
+        assert_eq!(request_auth(&headers, None).unwrap(), "Bearer xyz");
+        assert_eq!(
+            request_auth(&headers, Some("abc")).unwrap_err().status_code(),
+            http::StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn access_token_is_taken_out_of_form_fields() {
+        let mut fields = vec![
+            ("h".to_string(), "entry".to_string()),
+            ("access_token".to_string(), "abc".to_string()),
+            ("content".to_string(), "hello".to_string()),
+        ];
+        assert_eq!(take_access_token(&mut fields), Some("abc".into()));
+        assert_eq!(fields, vec![("h".into(), "entry".into()), ("content".into(), "hello".into())]);
+        assert_eq!(take_access_token(&mut fields), None);
+    }
 }
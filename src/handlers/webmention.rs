@@ -0,0 +1,377 @@
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::Form,
+    response::{IntoResponse, Response},
+};
+use diesel::prelude::*;
+use http::{header, StatusCode};
+use log::{error, info, warn};
+use serde::Deserialize;
+use tokio::sync::Semaphore;
+use url::{Host, Url};
+
+use crate::errors::*;
+use crate::handler::{MicropubDB, WithDB};
+use crate::handlers::micropub::slug_from_url;
+use crate::models::{NewMention, Post};
+use crate::schema::mentions;
+use crate::webmention::{parse_source, SourceMention};
+
+/// Sources larger than this aren't verified.
+const MAX_SOURCE_LENGTH: usize = 1024 * 1024; // 1 megabyte
+const SOURCE_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_SOURCE_REDIRECTS: usize = 5;
+/// Sources fetched at once, further webmentions are turned away until one finishes.
+const MAX_CONCURRENT_VERIFICATIONS: usize = 16;
+const DB_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Deserialize)]
+pub struct WebmentionForm {
+    source: String,
+    target: String,
+}
+
+/// Limits how many webmention sources are being fetched in the background.
+#[derive(Clone)]
+pub struct Verifications(Arc<Semaphore>);
+
+impl Default for Verifications {
+    fn default() -> Self {
+        Self(Arc::new(Semaphore::new(MAX_CONCURRENT_VERIFICATIONS)))
+    }
+}
+
+/// The result of fetching a webmention's source.
+#[derive(Debug, PartialEq)]
+enum SourceCheck {
+    Verified(SourceMention),
+    /// The source doesn't link to the target (any more)
+    NoLink,
+    /// The source responded with 410 Gone
+    Gone,
+}
+
+/// Receives a webmention. The target must be a post on this site, the source is fetched and
+/// checked in the background so the sender gets a 202 Accepted straight away. If too many sources
+/// are already being checked the sender gets a 503 Service Unavailable and should try again later.
+///
+/// A mention that's sent again keeps its status until its source has been checked again.
+#[tracing::instrument(level = "info", skip(verifications, db, site_config))]
+pub async fn handle_webmention(
+    Form(form): Form<WebmentionForm>,
+    verifications: Verifications,
+    db: Arc<MicropubDB>,
+    site_config: Arc<crate::MicropubSiteConfig>,
+) -> Result<Response, MicropubError> {
+    let source = http_url(&form.source, "source")?;
+    let mut target = http_url(&form.target, "target")?;
+    if source == target {
+        return Err(MicropubError::invalid_request("source and target must be different"));
+    }
+    target.set_fragment(None);
+
+    let mut target_path = target.clone();
+    target_path.set_query(None);
+    let slug = slug_from_url(&site_config, target_path.as_str())?.trim_matches('/');
+
+    let now = chrono::Utc::now().format(DB_DATETIME_FORMAT).to_string();
+    let mut conn = db.dbconn()?;
+    let post: Post = Post::published_by_slug(slug, false)
+        .first::<Post>(&mut conn)
+        .optional()?
+        .ok_or_else(|| {
+            warn!("webmention target {:?} is not a post", target);
+            MicropubError::invalid_request("target is not a post on this site")
+        })?;
+
+    let Ok(permit) = verifications.0.try_acquire_owned() else {
+        warn!("too many webmentions being verified, turning away {:?}", source);
+        return Ok((StatusCode::SERVICE_UNAVAILABLE, [(header::RETRY_AFTER, "60")]).into_response());
+    };
+
+    diesel::insert_into(mentions::table)
+        .values(NewMention {
+            post_id: post.id,
+            source: source.as_str(),
+            target: target.as_str(),
+        })
+        .on_conflict((mentions::source, mentions::target))
+        .do_update()
+        .set((mentions::post_id.eq(post.id), mentions::updated_at.eq(&now)))
+        .execute(&mut conn)?;
+    let mention_id: i32 = mentions::table
+        .select(mentions::id)
+        .filter(mentions::source.eq(source.as_str()))
+        .filter(mentions::target.eq(target.as_str()))
+        .first(&mut conn)?;
+    info!("queued webmention {} from {:?} to {:?}", mention_id, source, target);
+
+    tokio::spawn(async move {
+        verify_mention(db, mention_id, source, target).await;
+        drop(permit);
+    });
+
+    Ok(StatusCode::ACCEPTED.into_response())
+}
+
+fn http_url(url: &str, name: &str) -> Result<Url, MicropubError> {
+    Url::parse(url)
+        .ok()
+        .filter(|u| matches!(u.scheme(), "http" | "https"))
+        .ok_or_else(|| MicropubError::invalid_request(format!("{} must be an http(s) url", name)))
+}
+
+/// Fetches the source of a mention and records whether it is verified. Mentions whose
+/// source no longer links to the target are rejected, and deleted if the source is gone. If the
+/// source can't be fetched (e.g. it timed out or responded with an error) the mention is left as
+/// it was, and is checked again when the source sends it again.
+async fn verify_mention(
+    db: Arc<MicropubDB>,
+    mention_id: i32,
+    source: Url,
+    target: Url,
+) {
+    let check = match fetch_source(&source, &target, lookup_host, false).await {
+        Ok(check) => check,
+        Err(e) => {
+            warn!("could not fetch source of webmention {} from {:?}: {:?}", mention_id, source, e);
+            return;
+        }
+    };
+    info!("webmention {} from {:?}: {:?}", mention_id, source, check);
+
+    let result = db.dbconn().and_then(|mut conn| {
+        let mention = diesel::update(mentions::table.find(mention_id));
+        let now = chrono::Utc::now().format(DB_DATETIME_FORMAT).to_string();
+        match check {
+            SourceCheck::Verified(m) => mention
+                .set((
+                    mentions::status.eq("verified"),
+                    mentions::mention_type.eq(m.mention_type),
+                    mentions::author_name.eq(m.author_name),
+                    mentions::author_url.eq(m.author_url),
+                    mentions::author_photo.eq(m.author_photo),
+                    mentions::content.eq(m.content),
+                    mentions::updated_at.eq(&now),
+                ))
+                .execute(&mut conn),
+            SourceCheck::NoLink => mention
+                .set((mentions::status.eq("rejected"), mentions::updated_at.eq(&now)))
+                .execute(&mut conn),
+            SourceCheck::Gone => mention
+                .set((mentions::status.eq("deleted"), mentions::updated_at.eq(&now)))
+                .execute(&mut conn),
+        }
+        .map_err(MicropubError::from)
+    });
+    if let Err(e) = result {
+        error!("error recording webmention {} verification: {:?}", mention_id, e);
+    }
+}
+
+/// Fetches the source, following redirects, and looks for the link to the target. Unless
+/// `allow_private_hosts` is set (for tests), sources and redirects to hosts on loopback, private or
+/// link-local addresses are refused so that webmentions can't be used to reach services on this
+/// server's network, such as the blobject store.
+///
+/// Each host is resolved once with `lookup` and connected to at the addresses that were checked,
+/// so that it can't resolve to a public address when checked and a private one when fetched.
+async fn fetch_source<L, F>(
+    source: &Url,
+    target: &Url,
+    lookup: L,
+    allow_private_hosts: bool,
+) -> Result<SourceCheck, anyhow::Error>
+where
+    L: Fn(String, u16) -> F,
+    F: Future<Output = io::Result<Vec<SocketAddr>>>,
+{
+    let mut url = source.clone();
+    let mut redirects = 0;
+    let mut r = loop {
+        let port = url.port_or_known_default().unwrap_or(80);
+        let (domain, addrs) = match url.host() {
+            Some(Host::Domain(domain)) => {
+                (Some(domain.to_string()), lookup(domain.to_string(), port).await?)
+            }
+            Some(Host::Ipv4(ip)) => (None, vec![SocketAddr::new(ip.into(), port)]),
+            Some(Host::Ipv6(ip)) => (None, vec![SocketAddr::new(ip.into(), port)]),
+            None => anyhow::bail!("{} has no host", url),
+        };
+        if addrs.is_empty() {
+            anyhow::bail!("{} did not resolve to any addresses", url);
+        }
+        if !allow_private_hosts
+            && let Some(addr) = addrs.iter().find(|addr| !is_public_address(addr.ip()))
+        {
+            anyhow::bail!("not fetching {}, it resolves to {}", url, addr.ip());
+        }
+
+        // redirects are followed above, after checking where they lead
+        let mut client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none());
+        if let Some(domain) = &domain {
+            client = client.resolve_to_addrs(domain, &addrs);
+        }
+        let r = client
+            .build()?
+            .get(url.clone())
+            .header("accept", "text/html")
+            .timeout(SOURCE_FETCH_TIMEOUT)
+            .send()
+            .await?;
+        if !r.status().is_redirection() {
+            break r;
+        }
+
+        redirects += 1;
+        if redirects > MAX_SOURCE_REDIRECTS {
+            anyhow::bail!("source redirected more than {} times", MAX_SOURCE_REDIRECTS);
+        }
+        let location = r
+            .headers()
+            .get("location")
+            .and_then(|l| l.to_str().ok())
+            .ok_or_else(|| anyhow::anyhow!("redirect from {} without a location", url))?;
+        url = url.join(location)?;
+    };
+
+    if r.status() == reqwest::StatusCode::GONE {
+        return Ok(SourceCheck::Gone);
+    }
+    r = r.error_for_status()?;
+    if r.content_length().is_some_and(|len| len as usize > MAX_SOURCE_LENGTH) {
+        anyhow::bail!("source is larger than {} bytes", MAX_SOURCE_LENGTH);
+    }
+
+    // Content-Length may be missing (or wrong), so the limit is checked as the body is read
+    let mut body = Vec::new();
+    while let Some(chunk) = r.chunk().await? {
+        if body.len() + chunk.len() > MAX_SOURCE_LENGTH {
+            anyhow::bail!("source is larger than {} bytes", MAX_SOURCE_LENGTH);
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    let html = String::from_utf8_lossy(&body);
+    Ok(parse_source(&html, &url, target).map_or(SourceCheck::NoLink, SourceCheck::Verified))
+}
+
+async fn lookup_host(host: String, port: u16) -> io::Result<Vec<SocketAddr>> {
+    Ok(tokio::net::lookup_host((host.as_str(), port)).await?.collect())
+}
+
+/// Whether an address is on the public internet rather than loopback, a private network,
+/// link-local, etc.
+fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // shared address space (carrier-grade NAT), 100.64.0.0/10
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            match ip.to_ipv4_mapped() {
+                Some(ip) => is_public_address(ip.into()),
+                None => !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // unique local, fc00::/7
+                    || (first & 0xfe00) == 0xfc00
+                    // link-local, fe80::/10
+                    || (first & 0xffc0) == 0xfe80),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fetch_source, is_public_address, lookup_host, SourceCheck};
+    use std::net::SocketAddr;
+    use axum::{http::StatusCode, response::Redirect, routing::get, Router};
+    use url::Url;
+
+    #[tokio::test]
+    async fn fetch_source_from_stand_in() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let app = Router::new()
+            .route("/like", get(|| async {
+                axum::response::Html(r#"<div class="h-entry"><a class="u-like-of" href="https://example.com/hello"></a></div>"#)
+            }))
+            .route("/unrelated", get(|| async { axum::response::Html("<p>nothing to see</p>") }))
+            .route("/gone", get(|| async { StatusCode::GONE }))
+            .route("/moved", get(|| async { Redirect::permanent("/like") }))
+            .route("/loop", get(|| async { Redirect::temporary("/loop") }))
+            .route("/huge", get(|| async {
+                // streamed, so there's no Content-Length
+                let chunks = (0..100).map(|_| Ok::<_, std::io::Error>("<p>padding</p>".repeat(1024)));
+                axum::body::Body::from_stream(futures::stream::iter(chunks))
+            }));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let target = Url::parse("https://example.com/hello").unwrap();
+
+        for path in ["like", "moved"] {
+            match fetch_source(&base.join(path).unwrap(), &target, lookup_host, true).await.unwrap() {
+                SourceCheck::Verified(m) => assert_eq!(m.mention_type, "like"),
+                check => panic!("unexpected {:?}", check),
+            }
+        }
+        assert_eq!(
+            fetch_source(&base.join("unrelated").unwrap(), &target, lookup_host, true).await.unwrap(),
+            SourceCheck::NoLink
+        );
+        assert_eq!(
+            fetch_source(&base.join("gone").unwrap(), &target, lookup_host, true).await.unwrap(),
+            SourceCheck::Gone
+        );
+        for path in ["missing", "loop", "huge"] {
+            assert!(fetch_source(&base.join(path).unwrap(), &target, lookup_host, true).await.is_err());
+        }
+
+        // the stand in is on a loopback address
+        assert!(fetch_source(&base.join("like").unwrap(), &target, lookup_host, false).await.is_err());
+
+        // a name is fetched from the address it was looked up at, which has to be public
+        let stand_in = base.socket_addrs(|| None).unwrap()[0];
+        let lookup = |_: String, _: u16| async move { Ok::<_, std::io::Error>(vec![stand_in]) };
+        let by_name = Url::parse(&format!("http://stand-in.invalid:{}/like", stand_in.port())).unwrap();
+        assert!(matches!(
+            fetch_source(&by_name, &target, lookup, true).await.unwrap(),
+            SourceCheck::Verified(_)
+        ));
+        assert!(fetch_source(&by_name, &target, lookup, false).await.is_err());
+        let mixed = |_: String, port: u16| async move {
+            Ok::<_, std::io::Error>(vec![SocketAddr::from(([93, 184, 215, 14], port)), stand_in])
+        };
+        assert!(fetch_source(&by_name, &target, mixed, false).await.is_err());
+    }
+
+    #[test]
+    fn public_addresses() {
+        for ip in ["93.184.215.14", "2606:2800:21f:cb07:6820:80da:af6b:8b2c"] {
+            assert!(is_public_address(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "0.0.0.0",
+            "100.64.0.1", "::1", "::", "fd00::1", "fe80::1", "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_address(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Helpers for the optional built in IndieAuth server (https://indieauth.spec.indieweb.org/).
+//!
+//! Authorization codes and access tokens are random strings handed to the client once. Only
+//! their sha256 hashes are stored, in the auth_codes and access_tokens tables.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use diesel::prelude::*;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::auth::TokenValidateResponse;
+use crate::models::AccessToken;
+
+/// How long an authorization code can be redeemed for after it is issued.
+pub const AUTH_CODE_LIFETIME_MINUTES: i64 = 10;
+
+/// A new authorization code or access token: 32 random bytes, base64url encoded.
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// The hex encoded sha256 hash of a code or token, as stored in the database.
+pub fn token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Checks a PKCE code_verifier against the S256 code_challenge it was issued for.
+pub fn verify_pkce(code_verifier: &str, code_challenge: &str) -> bool {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes())) == code_challenge
+}
+
+/// Compares a credential entered on the consent page to the configured one without
+/// short-circuiting on the first differing byte.
+pub fn credential_matches(given: &str, expected: &str) -> bool {
+    let given = Sha256::digest(given.as_bytes());
+    let expected = Sha256::digest(expected.as_bytes());
+    given.iter().zip(expected.iter()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// The parameters a client sends to the authorization endpoint, which are carried through the
+/// consent page.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AuthorizationRequest {
+    pub response_type: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub state: String,
+    pub code_challenge: String,
+    pub code_challenge_method: String,
+    #[serde(default)]
+    pub scope: String,
+}
+
+impl AuthorizationRequest {
+    /// Returns a description of the problem if the request can't be approved. Only redirect_uris
+    /// on the same origin as the client_id are accepted, the client's metadata isn't fetched to
+    /// look for others.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.response_type != "code" {
+            return Err(format!("unsupported response_type '{}'", self.response_type));
+        }
+        if self.code_challenge_method != "S256" {
+            return Err(format!(
+                "unsupported code_challenge_method '{}'",
+                self.code_challenge_method
+            ));
+        }
+        if self.code_challenge.is_empty() {
+            return Err("missing code_challenge".into());
+        }
+
+        let client_id = url::Url::parse(&self.client_id)
+            .map_err(|_| format!("client_id '{}' is not a url", self.client_id))?;
+        if !matches!(client_id.scheme(), "http" | "https") {
+            return Err(format!("client_id '{}' is not an http(s) url", self.client_id));
+        }
+        let redirect_uri = url::Url::parse(&self.redirect_uri)
+            .map_err(|_| format!("redirect_uri '{}' is not a url", self.redirect_uri))?;
+        if redirect_uri.origin() != client_id.origin() {
+            return Err(format!(
+                "redirect_uri '{}' is not on the same origin as client_id '{}'",
+                self.redirect_uri, self.client_id
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Looks up the access token in an Authorization header value (`Bearer <token>`) among those
+/// issued by the built in server, returning None if it is unknown or revoked.
+pub fn validate_token(
+    conn: &mut SqliteConnection,
+    config: &crate::MicropubConfig,
+    auth: &str,
+) -> QueryResult<Option<TokenValidateResponse>> {
+    let Some(token) = auth.strip_prefix("Bearer ") else {
+        return Ok(None);
+    };
+
+    let access_token = AccessToken::active(&token_hash(token.trim()))
+        .first::<AccessToken>(conn)
+        .optional()?;
+
+    Ok(access_token.map(|t| TokenValidateResponse {
+        me: config.host_website.clone(),
+        client_id: t.client_id,
+        issued_at: chrono::NaiveDateTime::parse_from_str(&t.created_at, "%Y-%m-%d %H:%M:%S")
+            .map(|dt| dt.and_utc().timestamp())
+            .unwrap_or_default(),
+        scope: t.scope,
+        nonce: t.id.into(),
+        exp: None,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{credential_matches, random_token, token_hash, verify_pkce, AuthorizationRequest};
+
+    fn request(client_id: &str, redirect_uri: &str) -> AuthorizationRequest {
+        AuthorizationRequest {
+            response_type: "code".into(),
+            client_id: client_id.into(),
+            redirect_uri: redirect_uri.into(),
+            state: "1234".into(),
+            code_challenge: "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM".into(),
+            code_challenge_method: "S256".into(),
+            scope: "create".into(),
+        }
+    }
+
+    #[test]
+    fn pkce_s256() {
+        // https://datatracker.ietf.org/doc/html/rfc7636#appendix-B
+        let challenge = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+        assert!(verify_pkce("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk", challenge));
+        assert!(!verify_pkce("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXK", challenge));
+    }
+
+    #[test]
+    fn tokens_are_random_and_hashed() {
+        let token = random_token();
+        assert_eq!(token.len(), 43);
+        assert_ne!(token, random_token());
+        assert_eq!(token_hash(&token).len(), 64);
+        assert_eq!(token_hash(&token), token_hash(&token));
+        assert!(credential_matches("secret", "secret"));
+        assert!(!credential_matches("secret", "Secret"));
+    }
+
+    #[test]
+    fn authorization_request_validation() {
+        assert!(request("https://app.example.com/", "https://app.example.com/callback").validate().is_ok());
+        assert!(request("https://app.example.com/", "https://evil.example.com/callback").validate().is_err());
+        assert!(request("https://app.example.com/", "http://app.example.com/callback").validate().is_err());
+
+        let mut plain = request("https://app.example.com/", "https://app.example.com/callback");
+        plain.code_challenge_method = "plain".into();
+        assert!(plain.validate().is_err());
+    }
+}
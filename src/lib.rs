@@ -12,14 +12,17 @@ pub mod auth;
 pub mod config;
 pub mod constants;
 pub mod errors;
+pub mod fake_auth;
 pub mod handler;
 pub mod handlers;
+pub mod indieauth;
 pub mod media_util;
 pub mod models;
 pub mod post_util;
 pub mod schema;
 pub mod templates;
 pub mod view_models;
+pub mod webmention;
 
 pub use crate::config::*;
 pub use crate::constants::*;
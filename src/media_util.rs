@@ -3,7 +3,7 @@ use std::sync::Once;
 use magick_rust::{magick_wand_genesis, MagickWand};
 use mime;
 
-use crate::errors::MediaStripError;
+use crate::errors::MicropubError;
 
 static START: Once = Once::new();
 
@@ -23,7 +23,7 @@ pub fn guess_format(content_type: &Option<&str>) -> Option<String> {
     }
 }
 
-pub fn strip_media(contents: &[u8], format: &str) -> Result<Vec<u8>, MediaStripError> {
+pub fn strip_media(contents: &[u8], format: &str) -> Result<Vec<u8>, MicropubError> {
     START.call_once(|| {
         magick_wand_genesis();
     });
@@ -17,6 +17,14 @@ type AllColumns = (
     posts::updated_at,
     posts::content_type,
     posts::bookmark_of,
+    posts::deleted_at,
+    posts::summary,
+    posts::in_reply_to,
+    posts::like_of,
+    posts::repost_of,
+    posts::extra_properties,
+    posts::post_status,
+    posts::visibility,
 );
 
 const ALL_COLUMNS: AllColumns = (
@@ -30,6 +38,14 @@ const ALL_COLUMNS: AllColumns = (
     posts::updated_at,
     posts::content_type,
     posts::bookmark_of,
+    posts::deleted_at,
+    posts::summary,
+    posts::in_reply_to,
+    posts::like_of,
+    posts::repost_of,
+    posts::extra_properties,
+    posts::post_status,
+    posts::visibility,
 );
 
 type PostSqlType = <AllColumns as Expression>::SqlType;
@@ -55,6 +71,17 @@ pub struct Post {
     pub updated_at: String,
     pub content_type: Option<String>,
     pub bookmark_of: Option<String>,
+    pub deleted_at: Option<String>,
+    pub summary: Option<String>,
+    pub in_reply_to: Option<String>,
+    pub like_of: Option<String>,
+    pub repost_of: Option<String>,
+    /// mf2 properties without a column of their own, as a json object
+    pub extra_properties: Option<String>,
+    /// published or draft
+    pub post_status: String,
+    /// public, unlisted or private
+    pub visibility: String,
 }
 
 impl Post {
@@ -63,7 +90,43 @@ impl Post {
         Post::all().filter(slug.eq(url_slug))
     }
 
+    /// Posts that have been published: not deleted, not drafts and with a published date that has
+    /// passed. Scheduled posts appear once their time comes, as created_at is stored in the same
+    /// UTC format as sqlite's CURRENT_TIMESTAMP.
+    pub fn published<'a>() -> BoxedPostsQuery<'a> {
+        use crate::schema::posts::dsl::*;
+        Post::all()
+            .filter(post_status.eq("published"))
+            .filter(created_at.le(diesel::dsl::sql::<diesel::sql_types::Text>("CURRENT_TIMESTAMP")))
+    }
+
+    /// Published posts for listings and feeds, which leave out unlisted and private posts.
+    pub fn public<'a>() -> BoxedPostsQuery<'a> {
+        use crate::schema::posts::dsl::*;
+        Post::published().filter(visibility.eq("public"))
+    }
+
+    /// A published post at its slug. Unlisted posts are included, private posts only when the
+    /// viewer is authorized.
+    pub fn published_by_slug<'a>(url_slug: &'a str, include_private: bool) -> BoxedPostsQuery<'a> {
+        use crate::schema::posts::dsl::*;
+        let query = Post::published().filter(slug.eq(url_slug));
+        if include_private {
+            query
+        } else {
+            query.filter(visibility.ne("private"))
+        }
+    }
+
+    /// All posts that have not been deleted.
     pub fn all<'a>() -> BoxedPostsQuery<'a> {
+        use crate::schema::posts::dsl::*;
+        Post::all_including_deleted().filter(deleted_at.is_null())
+    }
+
+    /// All posts, including those removed by a Micropub delete action. Only use this where
+    /// deleted posts must be visible, e.g. for undelete or for serving 410 Gone.
+    pub fn all_including_deleted<'a>() -> BoxedPostsQuery<'a> {
         use crate::schema::posts::dsl::*;
         posts
             .select(ALL_COLUMNS)
@@ -71,17 +134,76 @@ impl Post {
             .into_boxed()
     }
 
+    pub fn by_slug_including_deleted<'a>(url_slug: &'a str) -> BoxedPostsQuery<'a> {
+        use crate::schema::posts::dsl::*;
+        Post::all_including_deleted().filter(slug.eq(url_slug))
+    }
+
+    /// The post that used to be at `old_slug`, before it was renamed. The redirects table is
+    /// filled by a trigger whenever a post's slug changes. As with published_by_slug, only
+    /// published posts are found, and private posts only when the viewer is authorized.
+    pub fn redirected_from<'a>(old_slug: &'a str, include_private: bool) -> BoxedPostsQuery<'a> {
+        use crate::schema::posts::dsl::*;
+        let redirected_ids = redirects::table
+            .select(redirects::post_id)
+            .filter(redirects::from_slug.eq(old_slug));
+        let query = Post::published().filter(id.eq_any(redirected_ids));
+        if include_private {
+            query
+        } else {
+            query.filter(visibility.ne("private"))
+        }
+    }
+
+    pub fn deleted_by_slug<'a>(url_slug: &'a str) -> BoxedPostsQuery<'a> {
+        use crate::schema::posts::dsl::*;
+        Post::by_slug_including_deleted(url_slug).filter(deleted_at.is_not_null())
+    }
+
     // TODO make tag lookup case insensitive?
     pub fn by_tag<'a>(tag: &'a str) -> BoxedPostsQuery<'a> {
         use crate::schema::posts::dsl::*;
-        Post::all()
+        Post::public()
             .filter(id.eq_any(posts_for_category(tag)))
             .order_by(created_at.desc())
     }
 
+    /// Posts of the given type, following https://indieweb.org/post-type-discovery loosely. Returns
+    /// None for post types we don't know how to find.
+    pub fn by_post_type<'a>(post_type: &str) -> Option<BoxedPostsQuery<'a>> {
+        use crate::schema::posts::dsl::*;
+        let photo_post_ids = photos::table.select(photos::post_id);
+        match post_type {
+            "photo" => Some(Post::all().filter(id.eq_any(photo_post_ids))),
+            "bookmark" => Some(Post::all().filter(bookmark_of.is_not_null())),
+            "reply" => Some(Post::all().filter(in_reply_to.is_not_null())),
+            "like" => Some(Post::all().filter(like_of.is_not_null())),
+            "repost" => Some(Post::all().filter(repost_of.is_not_null())),
+            "article" => Some(
+                Post::all()
+                    .filter(name.is_not_null())
+                    .filter(bookmark_of.is_null())
+                    .filter(in_reply_to.is_null())
+                    .filter(like_of.is_null())
+                    .filter(repost_of.is_null())
+                    .filter(id.ne_all(photo_post_ids))
+            ),
+            "note" => Some(
+                Post::all()
+                    .filter(name.is_null())
+                    .filter(bookmark_of.is_null())
+                    .filter(in_reply_to.is_null())
+                    .filter(like_of.is_null())
+                    .filter(repost_of.is_null())
+                    .filter(id.ne_all(photo_post_ids))
+            ),
+            _ => None,
+        }
+    }
+
     pub fn latest<'a>() -> BoxedPostsQuery<'a> {
         use crate::schema::posts::dsl::*;
-        Post::all().order_by(created_at.desc()).limit(1)
+        Post::public().order_by(created_at.desc()).limit(1)
     }
 }
 
@@ -97,6 +219,13 @@ pub struct NewPost<'a> {
     pub created_at: Option<&'a str>,
     pub updated_at: Option<&'a str>,
     pub bookmark_of: Option<&'a str>,
+    pub summary: Option<&'a str>,
+    pub in_reply_to: Option<&'a str>,
+    pub like_of: Option<&'a str>,
+    pub repost_of: Option<&'a str>,
+    pub extra_properties: Option<&'a str>,
+    pub post_status: Option<&'a str>,
+    pub visibility: Option<&'a str>,
 }
 
 #[derive(Debug, Insertable)]
@@ -112,6 +241,14 @@ pub struct NewPostHistory {
     pub updated_at: String,
     pub content_type: Option<String>,
     pub bookmark_of: Option<String>,
+    pub deleted_at: Option<String>,
+    pub summary: Option<String>,
+    pub in_reply_to: Option<String>,
+    pub like_of: Option<String>,
+    pub repost_of: Option<String>,
+    pub extra_properties: Option<String>,
+    pub post_status: String,
+    pub visibility: String,
 }
 
 impl From<Post> for NewPostHistory {
@@ -127,6 +264,14 @@ impl From<Post> for NewPostHistory {
             updated_at: post.updated_at,
             content_type: post.content_type,
             bookmark_of: post.bookmark_of,
+            deleted_at: post.deleted_at,
+            summary: post.summary,
+            in_reply_to: post.in_reply_to,
+            like_of: post.like_of,
+            repost_of: post.repost_of,
+            extra_properties: post.extra_properties,
+            post_status: post.post_status,
+            visibility: post.visibility,
         }
     }
 }
@@ -160,3 +305,251 @@ pub struct NewPhoto<'a> {
     pub url: &'a str,
     pub alt: Option<&'a str>,
 }
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = syndications)]
+pub struct NewSyndication<'a> {
+    pub post_id: i32,
+    pub target: &'a str,
+}
+
+#[derive(Clone, Debug, Queryable, Serialize)]
+pub struct Event {
+    pub id: i32,
+    pub post_id: i32,
+    pub starts_at: String,
+    pub ends_at: Option<String>,
+    pub location: Option<String>,
+    pub url: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Event {
+    /// Events of posts that have not been deleted, most recent start first.
+    pub fn all<'a>() -> events::BoxedQuery<'a, Sqlite> {
+        let live_post_ids = posts::table
+            .select(posts::id)
+            .filter(posts::deleted_at.is_null());
+        events::table
+            .filter(events::post_id.eq_any(live_post_ids))
+            .order_by(events::starts_at.desc())
+            .into_boxed()
+    }
+
+    pub fn for_posts<'a>(post_ids: &'a [i32]) -> events::BoxedQuery<'a, Sqlite> {
+        events::table
+            .filter(events::post_id.eq_any(post_ids))
+            .into_boxed()
+    }
+
+    /// Whether the event has not yet finished as of `now`, a UTC datetime in the
+    /// `%Y-%m-%d %H:%M:%S` format that starts_at and ends_at are stored in.
+    pub fn is_upcoming(&self, now: &str) -> bool {
+        self.ends_at.as_deref().unwrap_or(&self.starts_at) >= now
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = events)]
+pub struct NewEvent<'a> {
+    pub post_id: i32,
+    pub starts_at: &'a str,
+    pub ends_at: Option<&'a str>,
+    pub location: Option<&'a str>,
+    pub url: Option<&'a str>,
+}
+
+#[derive(Clone, Debug, Queryable, Serialize)]
+pub struct Review {
+    pub id: i32,
+    pub post_id: i32,
+    pub item_name: Option<String>,
+    pub item_url: Option<String>,
+    pub rating: Option<f64>,
+    pub best: Option<f64>,
+    pub worst: Option<f64>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Review {
+    pub fn for_posts<'a>(post_ids: &'a [i32]) -> reviews::BoxedQuery<'a, Sqlite> {
+        reviews::table
+            .filter(reviews::post_id.eq_any(post_ids))
+            .into_boxed()
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = reviews)]
+pub struct NewReview<'a> {
+    pub post_id: i32,
+    pub item_name: Option<&'a str>,
+    pub item_url: Option<&'a str>,
+    pub rating: Option<f64>,
+    pub best: Option<f64>,
+    pub worst: Option<f64>,
+}
+
+/// A nested microformats object of a post, e.g. the h-food of a drank property. object is the mf2
+/// json of the object.
+#[derive(Clone, Debug, Queryable, Serialize)]
+pub struct PostObject {
+    pub id: i32,
+    pub post_id: i32,
+    pub property: String,
+    pub object: String,
+    pub created_at: String,
+}
+
+impl PostObject {
+    pub fn for_posts<'a>(post_ids: &'a [i32]) -> post_objects::BoxedQuery<'a, Sqlite> {
+        post_objects::table
+            .filter(post_objects::post_id.eq_any(post_ids))
+            .order_by(post_objects::id.asc())
+            .into_boxed()
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = post_objects)]
+pub struct NewPostObject<'a> {
+    pub post_id: i32,
+    pub property: &'a str,
+    pub object: &'a str,
+}
+
+/// The checkin venue or location of a post, property is either "checkin" or "location".
+#[derive(Clone, Debug, Queryable, Serialize)]
+pub struct Location {
+    pub id: i32,
+    pub post_id: i32,
+    pub property: String,
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub created_at: String,
+}
+
+impl Location {
+    pub fn for_posts<'a>(post_ids: &'a [i32]) -> locations::BoxedQuery<'a, Sqlite> {
+        locations::table
+            .filter(locations::post_id.eq_any(post_ids))
+            .into_boxed()
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = locations)]
+pub struct NewLocation<'a> {
+    pub post_id: i32,
+    pub property: &'a str,
+    pub name: Option<&'a str>,
+    pub url: Option<&'a str>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+/// An authorization code issued by the built in IndieAuth server, see crate::indieauth.
+#[derive(Clone, Debug, Queryable)]
+pub struct AuthCode {
+    pub id: i32,
+    pub code_hash: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub code_challenge: String,
+    pub expires_at: String,
+    pub used_at: Option<String>,
+    pub created_at: String,
+}
+
+impl AuthCode {
+    /// The unused, unexpired code with the given hash. `now` is a UTC datetime in the
+    /// `%Y-%m-%d %H:%M:%S` format that expires_at is stored in.
+    pub fn redeemable<'a>(hash: &'a str, now: &'a str) -> auth_codes::BoxedQuery<'a, Sqlite> {
+        auth_codes::table
+            .filter(auth_codes::code_hash.eq(hash))
+            .filter(auth_codes::used_at.is_null())
+            .filter(auth_codes::expires_at.gt(now))
+            .into_boxed()
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = auth_codes)]
+pub struct NewAuthCode<'a> {
+    pub code_hash: &'a str,
+    pub client_id: &'a str,
+    pub redirect_uri: &'a str,
+    pub scope: &'a str,
+    pub code_challenge: &'a str,
+    pub expires_at: &'a str,
+}
+
+/// An access token issued by the built in IndieAuth server.
+#[derive(Clone, Debug, Queryable)]
+pub struct AccessToken {
+    pub id: i32,
+    pub token_hash: String,
+    pub client_id: String,
+    pub scope: String,
+    pub revoked_at: Option<String>,
+    pub created_at: String,
+}
+
+impl AccessToken {
+    /// The token with the given hash, unless it has been revoked.
+    pub fn active(hash: &str) -> access_tokens::BoxedQuery<'_, Sqlite> {
+        access_tokens::table
+            .filter(access_tokens::token_hash.eq(hash))
+            .filter(access_tokens::revoked_at.is_null())
+            .into_boxed()
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = access_tokens)]
+pub struct NewAccessToken<'a> {
+    pub token_hash: &'a str,
+    pub client_id: &'a str,
+    pub scope: &'a str,
+}
+
+/// A webmention received for a post, see crate::webmention.
+#[derive(Clone, Debug, Queryable)]
+pub struct Mention {
+    pub id: i32,
+    pub post_id: i32,
+    pub source: String,
+    pub target: String,
+    pub status: String,
+    pub mention_type: String,
+    pub author_name: Option<String>,
+    pub author_url: Option<String>,
+    pub author_photo: Option<String>,
+    pub content: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Mention {
+    /// Verified mentions of the posts, oldest first.
+    pub fn verified_for_posts<'a>(post_ids: &'a [i32]) -> mentions::BoxedQuery<'a, Sqlite> {
+        mentions::table
+            .filter(mentions::post_id.eq_any(post_ids))
+            .filter(mentions::status.eq("verified"))
+            .order_by(mentions::created_at.asc())
+            .into_boxed()
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = mentions)]
+pub struct NewMention<'a> {
+    pub post_id: i32,
+    pub source: &'a str,
+    pub target: &'a str,
+}
@@ -24,6 +24,42 @@ pub fn get_slug(name: Option<&str>, now_fn: fn() -> DateTime<Local>) -> String {
     slug.replace(" ", "-")
 }
 
+/// Slug prefixes that are routed elsewhere, so posts under them could never be shown.
+const RESERVED_SLUG_PREFIXES: &[&str] = &["media/", "theme/", "tag/", "preview/", "feeds/", ".well-known/"];
+
+/// Paths that are routed elsewhere.
+const RESERVED_SLUGS: &[&str] = &["archives", "auth", "events", "media", "micropub", "revoke", "token", "webmention"];
+
+/// Normalizes a client supplied slug (mp-slug) by trimming whitespace and slashes, collapsing
+/// repeated slashes and replacing spaces with hyphens. Returns a description of the problem if the
+/// slug can't be used, e.g. because it contains '..' or would be shadowed by another route.
+pub fn normalize_slug(slug: &str) -> Result<String, String> {
+    let normalized = slug
+        .trim()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+        .replace(' ', "-");
+
+    if normalized.is_empty() {
+        return Err("slug is empty".into());
+    }
+    if normalized.split('/').any(|segment| segment == "." || segment == "..") {
+        return Err(format!("slug '{}' contains a relative path segment", slug));
+    }
+    if let Some(c) = normalized.chars().find(|c| c.is_control() || ['?', '#', '%', '\\'].contains(c)) {
+        return Err(format!("slug '{}' contains '{}'", slug, c.escape_default()));
+    }
+    if RESERVED_SLUGS.contains(&normalized.as_str())
+        || RESERVED_SLUG_PREFIXES.iter().any(|prefix| normalized.starts_with(prefix))
+    {
+        return Err(format!("slug '{}' is reserved", slug));
+    }
+
+    Ok(normalized)
+}
+
 #[tracing::instrument(level = "info")]
 pub fn get_local_datetime(
     datetime: &str,
@@ -37,9 +73,24 @@ pub fn get_local_datetime(
     })
 }
 
+/// Converts a client supplied published date into the UTC "%Y-%m-%d %H:%M:%S" format stored in the
+/// database. Accepts RFC 3339 dates (as sent by most clients) as well as dates already in the
+/// database format, which are assumed to be UTC.
+pub fn normalize_published(published: &str) -> Option<String> {
+    const DB_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(published) {
+        return Some(dt.naive_utc().format(DB_FORMAT).to_string());
+    }
+
+    chrono::NaiveDateTime::parse_from_str(published, DB_FORMAT)
+        .or_else(|_e| chrono::NaiveDateTime::parse_from_str(published, "%Y-%m-%dT%H:%M:%S"))
+        .ok()
+        .map(|ndt| ndt.format(DB_FORMAT).to_string())
+}
+
 #[cfg(test)]
 mod test {
-    use super::get_slug;
+    use super::{get_slug, normalize_published, normalize_slug};
 
     use chrono::{DateTime, Local, TimeZone};
 
@@ -76,4 +127,40 @@ mod test {
     fn it_truncates_content_for_slug() {
         assert_eq!(get_slug(None, now), "2020/10/24/203233");
     }
+
+    #[test]
+    fn it_normalizes_rfc3339_published_to_utc() {
+        assert_eq!(
+            normalize_published("2020-10-03T14:10:06-05:00"),
+            Some("2020-10-03 19:10:06".into())
+        );
+    }
+
+    #[test]
+    fn it_keeps_published_in_db_format() {
+        assert_eq!(
+            normalize_published("2020-04-04 15:30:00"),
+            Some("2020-04-04 15:30:00".into())
+        );
+    }
+
+    #[test]
+    fn it_rejects_unparseable_published() {
+        assert_eq!(normalize_published("yesterday"), None);
+    }
+
+    #[test]
+    fn it_normalizes_slugs() {
+        assert_eq!(normalize_slug("/2020//10/my post/"), Ok("2020/10/my-post".into()));
+        assert_eq!(normalize_slug(" hello-world "), Ok("hello-world".into()));
+        // only whole reserved segments are rejected
+        assert_eq!(normalize_slug("tagged/thing"), Ok("tagged/thing".into()));
+    }
+
+    #[test]
+    fn it_rejects_invalid_slugs() {
+        for slug in ["", "/", "a/../b", "./a", "media/1234", "/tag/x", "theme/site.css", "archives", "a?b=c"] {
+            assert!(normalize_slug(slug).is_err(), "{:?} should be rejected", slug);
+        }
+    }
 }
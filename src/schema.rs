@@ -1,5 +1,30 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    access_tokens (id) {
+        id -> Integer,
+        token_hash -> Text,
+        client_id -> Text,
+        scope -> Text,
+        revoked_at -> Nullable<Text>,
+        created_at -> Text,
+    }
+}
+
+diesel::table! {
+    auth_codes (id) {
+        id -> Integer,
+        code_hash -> Text,
+        client_id -> Text,
+        redirect_uri -> Text,
+        scope -> Text,
+        code_challenge -> Text,
+        expires_at -> Text,
+        used_at -> Nullable<Text>,
+        created_at -> Text,
+    }
+}
+
 diesel::table! {
     categories (id) {
         id -> Integer,
@@ -8,6 +33,32 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    events (id) {
+        id -> Integer,
+        post_id -> Integer,
+        starts_at -> Text,
+        ends_at -> Nullable<Text>,
+        location -> Nullable<Text>,
+        url -> Nullable<Text>,
+        created_at -> Text,
+        updated_at -> Text,
+    }
+}
+
+diesel::table! {
+    locations (id) {
+        id -> Integer,
+        post_id -> Integer,
+        property -> Text,
+        name -> Nullable<Text>,
+        url -> Nullable<Text>,
+        latitude -> Nullable<Double>,
+        longitude -> Nullable<Double>,
+        created_at -> Text,
+    }
+}
+
 diesel::table! {
     media (id) {
         id -> Integer,
@@ -19,6 +70,23 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    mentions (id) {
+        id -> Integer,
+        post_id -> Integer,
+        source -> Text,
+        target -> Text,
+        status -> Text,
+        mention_type -> Text,
+        author_name -> Nullable<Text>,
+        author_url -> Nullable<Text>,
+        author_photo -> Nullable<Text>,
+        content -> Nullable<Text>,
+        created_at -> Text,
+        updated_at -> Text,
+    }
+}
+
 diesel::table! {
     original_blobs (id) {
         id -> Integer,
@@ -36,6 +104,16 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    post_objects (id) {
+        id -> Integer,
+        post_id -> Integer,
+        property -> Text,
+        object -> Text,
+        created_at -> Text,
+    }
+}
+
 diesel::table! {
     post_history (id) {
         id -> Integer,
@@ -49,6 +127,14 @@ diesel::table! {
         updated_at -> Text,
         content_type -> Nullable<Text>,
         bookmark_of -> Nullable<Text>,
+        deleted_at -> Nullable<Text>,
+        summary -> Nullable<Text>,
+        in_reply_to -> Nullable<Text>,
+        like_of -> Nullable<Text>,
+        repost_of -> Nullable<Text>,
+        extra_properties -> Nullable<Text>,
+        post_status -> Text,
+        visibility -> Text,
     }
 }
 
@@ -64,18 +150,76 @@ diesel::table! {
         updated_at -> Text,
         content_type -> Nullable<Text>,
         bookmark_of -> Nullable<Text>,
+        deleted_at -> Nullable<Text>,
+        summary -> Nullable<Text>,
+        in_reply_to -> Nullable<Text>,
+        like_of -> Nullable<Text>,
+        repost_of -> Nullable<Text>,
+        extra_properties -> Nullable<Text>,
+        post_status -> Text,
+        visibility -> Text,
+    }
+}
+
+diesel::table! {
+    redirects (id) {
+        id -> Integer,
+        from_slug -> Text,
+        post_id -> Integer,
+        created_at -> Text,
+    }
+}
+
+diesel::table! {
+    reviews (id) {
+        id -> Integer,
+        post_id -> Integer,
+        item_name -> Nullable<Text>,
+        item_url -> Nullable<Text>,
+        rating -> Nullable<Double>,
+        best -> Nullable<Double>,
+        worst -> Nullable<Double>,
+        created_at -> Text,
+        updated_at -> Text,
+    }
+}
+
+diesel::table! {
+    syndications (id) {
+        id -> Integer,
+        post_id -> Integer,
+        target -> Text,
+        url -> Nullable<Text>,
+        created_at -> Text,
+        updated_at -> Text,
     }
 }
 
 diesel::joinable!(categories -> posts (post_id));
+diesel::joinable!(events -> posts (post_id));
+diesel::joinable!(locations -> posts (post_id));
+diesel::joinable!(mentions -> posts (post_id));
 diesel::joinable!(original_blobs -> posts (post_id));
 diesel::joinable!(photos -> posts (post_id));
+diesel::joinable!(post_objects -> posts (post_id));
+diesel::joinable!(redirects -> posts (post_id));
+diesel::joinable!(reviews -> posts (post_id));
+diesel::joinable!(syndications -> posts (post_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    access_tokens,
+    auth_codes,
     categories,
+    events,
+    locations,
     media,
+    mentions,
     original_blobs,
     photos,
     post_history,
+    post_objects,
     posts,
+    redirects,
+    reviews,
+    syndications,
 );
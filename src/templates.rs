@@ -19,31 +19,79 @@ impl Templates {
         }
     }
 
+    /// The built in atom feed template. Expects FEED_TITLE, SITE_URL (with a trailing slash),
+    /// SITE_DOMAIN and AUTHOR_NAME in the base context, and optionally AUTHOR_URL and AUTHOR_EMAIL.
     pub fn atom_default(base_ctx: TeraContext) -> Self {
         let atom_template = indoc! {r#"
         <?xml version="1.0" encoding="utf-8"?>
 
-        <feed xmlns="http://www.w3.org/2005/Atom">
-        <title>David's Blog</title>
-        <link href="https://davidwilemski.com/" rel="alternate"/>
-        <link href="https://davidwilemski.com/feeds/all.atom.xml" rel="self"/>
-        <id>https://davidwilemski.com/</id>
+        <feed xmlns="http://www.w3.org/2005/Atom" xmlns:thr="http://purl.org/syndication/thread/1.0">
+        <title>{{ FEED_TITLE }}</title>
+        <link href="{{ SITE_URL }}" rel="alternate"/>
+        <link href="{{ SITE_URL }}feeds/all.atom.xml" rel="self"/>
+        <id>{{ SITE_URL }}</id>
         <updated>{{updated_date}}</updated>
         {% for post in posts %}
           <entry>
-          <title>{% if post.bookmark_of %}🔖 {% endif %}{{ post.title }}</title>
-          <link href="/{{post.slug}}" rel="alternate"/>
+          <title>{% if post.bookmark_of %}🔖 {% elif post.in_reply_to %}↪️ {% elif post.like_of %}❤️ {% elif post.repost_of %}🔁 {% endif %}{{ post.title }}</title>
+          <link href="{{ SITE_URL }}{{post.slug}}" rel="alternate"/>
           <published>{{ post.published }}</published>
           <updated>{{ post.updated }}</updated>
           <author>
-            <name>David Wilemski</name>
+            <name>{{ AUTHOR_NAME }}</name>
+            {% if AUTHOR_URL %}<uri>{{ AUTHOR_URL }}</uri>{% endif %}
+            {% if AUTHOR_EMAIL %}<email>{{ AUTHOR_EMAIL }}</email>{% endif %}
           </author>
-          <id>tag:davidwilemski.com,{{ post.date.date }}:{{ post.slug }}</id>
+          <id>tag:{{ SITE_DOMAIN }},{{ post.date.date }}:{{ post.slug }}</id>
+          {% if post.in_reply_to %}
+          <thr:in-reply-to ref="{{ post.in_reply_to }}" href="{{ post.in_reply_to }}"/>
+          {% endif %}
           <content type="html" xml:lang="en">
+            {% if post.in_reply_to %}
+            <a class="u-in-reply-to" href="{{ post.in_reply_to }}" rel="nofollow">(↪️ in reply to)</a>
+            <br />
+            {% endif %}
+            {% if post.review %}
+            <div class="h-review">
+              <div class="p-item h-product">
+              {% if post.review.item_url %}<a class="u-url p-name" href="{{ post.review.item_url }}">{% if post.review.item_name %}{{ post.review.item_name }}{% else %}{{ post.review.item_url }}{% endif %}</a>{% else %}<span class="p-name">{{ post.review.item_name }}</span>{% endif %}
+              </div>
+              {% if post.review.rating %}
+              <p>Rating: <data class="p-rating" value="{{ post.review.rating }}">{{ post.review.rating }}</data> out of {% if post.review.best %}<data class="p-best" value="{{ post.review.best }}">{{ post.review.best }}</data>{% else %}5{% endif %}</p>
+              {% endif %}
+            </div>
+            {% endif %}
+            {% for object in post.objects %}
+            <p class="p-{{ object.property }} {{ object.kind }}">
+              {% if object.property == "drank" %}🍵 Drank{% elif object.property == "ate" %}🍽️ Ate{% else %}{{ object.property }}{% endif %}:
+              <span class="p-name">{{ object.name }}</span>
+            </p>
+            {% endfor %}
+            {% if post.checkin %}
+            <p>📍 Checked in at
+              <span class="p-checkin h-card">{% if post.checkin.url %}<a class="p-name u-url" href="{{ post.checkin.url }}">{{ post.checkin.name }}</a>{% else %}<span class="p-name">{{ post.checkin.name }}</span>{% endif %}</span>
+              {% if post.checkin.map_url %}(<a href="{{ post.checkin.map_url }}">map</a>){% endif %}
+            </p>
+            {% endif %}
             {{ post.content | safe}}
+            {% if post.location and post.location.map_url %}
+            <p class="p-location h-geo">
+              📍 <a href="{{ post.location.map_url }}">{% if post.location.name %}{{ post.location.name }}{% else %}{{ post.location.latitude }}, {{ post.location.longitude }}{% endif %}</a>
+              <data class="p-latitude" value="{{ post.location.latitude }}"></data>
+              <data class="p-longitude" value="{{ post.location.longitude }}"></data>
+            </p>
+            {% endif %}
             {% if post.bookmark_of %}
             <br />
-            <a href="{{ post.bookmark_of }}" rel="nofollow">(🔖 bookmark)</a>
+            <a class="u-bookmark-of" href="{{ post.bookmark_of }}" rel="nofollow">(🔖 bookmark)</a>
+            {% endif %}
+            {% if post.like_of %}
+            <br />
+            <a class="u-like-of" href="{{ post.like_of }}" rel="nofollow">(❤️ like)</a>
+            {% endif %}
+            {% if post.repost_of %}
+            <br />
+            <a class="u-repost-of" href="{{ post.repost_of }}" rel="nofollow">(🔁 repost)</a>
             {% endif %}
           </content>
           {% for tag in post.tags %}
@@ -60,6 +108,53 @@ impl Templates {
         Templates::new(Arc::new(tera), base_ctx)
     }
 
+    /// The built in IndieAuth consent page. Expects SITENAME and DEFAULT_LANG in the base context,
+    /// and `request` (an indieauth::AuthorizationRequest), `scopes` and an optional `error` added
+    /// when rendering.
+    pub fn consent_default(base_ctx: TeraContext) -> Self {
+        let consent_template = indoc! {r#"
+        <!DOCTYPE html>
+        <html lang="{{ DEFAULT_LANG }}">
+        <head>
+          <meta charset="utf-8">
+          <meta name="viewport" content="width=device-width, initial-scale=1">
+          <title>Authorize {{ request.client_id }} - {{ SITENAME }}</title>
+        </head>
+        <body>
+          <h1>Sign in to {{ SITENAME }}</h1>
+          {% if error %}
+          <p class="error">{{ error }}</p>
+          {% endif %}
+          <p><a href="{{ request.client_id }}">{{ request.client_id }}</a> is requesting access{% if scopes %} with the following scopes:{% else %} to confirm your identity.{% endif %}</p>
+          {% if scopes %}
+          <ul>
+            {% for scope in scopes %}
+            <li>{{ scope }}</li>
+            {% endfor %}
+          </ul>
+          {% endif %}
+          <p>You will be redirected to {{ request.redirect_uri }}</p>
+          <form method="post" action="/auth">
+            <input type="hidden" name="response_type" value="{{ request.response_type }}">
+            <input type="hidden" name="client_id" value="{{ request.client_id }}">
+            <input type="hidden" name="redirect_uri" value="{{ request.redirect_uri }}">
+            <input type="hidden" name="state" value="{{ request.state }}">
+            <input type="hidden" name="code_challenge" value="{{ request.code_challenge }}">
+            <input type="hidden" name="code_challenge_method" value="{{ request.code_challenge_method }}">
+            <input type="hidden" name="scope" value="{{ request.scope }}">
+            <label>Credential <input type="password" name="credential" autocomplete="current-password" required autofocus></label>
+            <button type="submit">Approve</button>
+          </form>
+        </body>
+        </html>
+        "#};
+        let mut tera = Tera::default();
+        tera.add_raw_template("consent.html", consent_template)
+            .expect("invalid consent template");
+
+        Templates::new(Arc::new(tera), base_ctx)
+    }
+
     pub fn add_context<T: Serialize + ?Sized>(&self, key: &str, val: &T) -> Templates {
         let mut new_ctx = self.ctx.clone();
         new_ctx.insert(key, val);
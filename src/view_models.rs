@@ -2,7 +2,8 @@ use markdown;
 use serde::{Deserialize, Serialize};
 use tracing::debug_span;
 
-use crate::models::Post as DBPost;
+use crate::models::{Event as DBEvent, Location as DBLocation, Mention as DBMention, Post as DBPost, PostObject as DBPostObject, Review as DBReview};
+use crate::post_util;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Date {
@@ -36,6 +37,143 @@ pub struct Photo {
     alt: Option<String>,
 }
 
+/// Details of an h-event post. start and end are local times in RFC3339 format.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Event {
+    pub start: String,
+    pub end: Option<String>,
+    pub location: Option<String>,
+    pub url: Option<String>,
+    pub date: Date,
+}
+
+impl Event {
+    pub fn new_from(
+        event: DBEvent,
+        offset: &chrono::FixedOffset,
+    ) -> Result<Self, chrono::format::ParseError> {
+        let start = post_util::get_local_datetime(&event.starts_at, offset)?;
+        let end = event
+            .ends_at
+            .as_deref()
+            .map(|e| post_util::get_local_datetime(e, offset))
+            .transpose()?;
+
+        Ok(Event {
+            start: start.to_rfc3339(),
+            end: end.map(|e| e.to_rfc3339()),
+            location: event.location,
+            url: event.url,
+            date: Date::from(&start),
+        })
+    }
+}
+
+/// The reviewed item and rating of an h-review post.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Review {
+    pub item_name: Option<String>,
+    pub item_url: Option<String>,
+    pub rating: Option<f64>,
+    pub best: Option<f64>,
+    pub worst: Option<f64>,
+}
+
+impl From<DBReview> for Review {
+    fn from(review: DBReview) -> Self {
+        Review {
+            item_name: review.item_name,
+            item_url: review.item_url,
+            rating: review.rating,
+            best: review.best,
+            worst: review.worst,
+        }
+    }
+}
+
+/// A verified webmention of a post.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Mention {
+    pub source: String,
+    pub author_name: Option<String>,
+    pub author_url: Option<String>,
+    pub author_photo: Option<String>,
+    pub content: Option<String>,
+    /// When the mention was received
+    pub received: String,
+}
+
+impl From<DBMention> for Mention {
+    fn from(mention: DBMention) -> Self {
+        Mention {
+            source: mention.source,
+            author_name: mention.author_name,
+            author_url: mention.author_url,
+            author_photo: mention.author_photo,
+            content: mention.content,
+            received: mention.created_at,
+        }
+    }
+}
+
+/// A nested microformats object such as the h-food of a drank property. kind and name are pulled
+/// out of the mf2 json for convenience, properties holds the object's full properties.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NestedObject {
+    pub property: String,
+    pub kind: Option<String>,
+    pub name: Option<String>,
+    pub properties: serde_json::Value,
+}
+
+impl TryFrom<DBPostObject> for NestedObject {
+    type Error = serde_json::Error;
+
+    fn try_from(object: DBPostObject) -> Result<Self, Self::Error> {
+        let mut mf2: serde_json::Value = serde_json::from_str(&object.object)?;
+        let kind = mf2["type"][0].as_str().map(String::from);
+        let properties = mf2["properties"].take();
+        // clients send either ["name"] or a bare "name"
+        let name = properties["name"][0]
+            .as_str()
+            .or(properties["name"].as_str())
+            .map(String::from);
+
+        Ok(NestedObject {
+            property: object.property,
+            kind,
+            name,
+            properties,
+        })
+    }
+}
+
+/// A checkin venue or location. map_url links to the coordinates on OpenStreetMap when they're
+/// known.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Location {
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub map_url: Option<String>,
+}
+
+impl From<DBLocation> for Location {
+    fn from(location: DBLocation) -> Self {
+        let map_url = location.latitude.zip(location.longitude).map(|(lat, long)| {
+            format!("https://www.openstreetmap.org/?mlat={lat}&mlon={long}#map=16/{lat}/{long}")
+        });
+        Location {
+            name: location.name,
+            url: location.url,
+            latitude: location.latitude,
+            longitude: location.longitude,
+            map_url,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Post {
     pub slug: String,
@@ -49,7 +187,24 @@ pub struct Post {
     pub tags: Vec<String>,
     pub date: Date,
     pub bookmark_of: Option<String>,
+    pub in_reply_to: Option<String>,
+    pub like_of: Option<String>,
+    pub repost_of: Option<String>,
     pub photos: Vec<Photo>,
+    pub event: Option<Event>,
+    pub review: Option<Review>,
+    pub objects: Vec<NestedObject>,
+    pub checkin: Option<Location>,
+    pub location: Option<Location>,
+    /// published or draft. Drafts are only rendered for previews.
+    pub post_status: String,
+    /// public, unlisted or private
+    pub visibility: String,
+    /// Verified webmentions, split up by type
+    pub replies: Vec<Mention>,
+    pub likes: Vec<Mention>,
+    pub reposts: Vec<Mention>,
+    pub mentions: Vec<Mention>,
 }
 
 impl Post {
@@ -83,10 +238,62 @@ impl Post {
             date,
             tags: categories,
             bookmark_of: post.bookmark_of,
+            in_reply_to: post.in_reply_to,
+            like_of: post.like_of,
+            repost_of: post.repost_of,
             photos: internal_photos
                 .drain(..)
                 .map(|(url, alt)| Photo { url, alt })
                 .collect(),
+            event: None,
+            review: None,
+            objects: vec![],
+            checkin: None,
+            location: None,
+            post_status: post.post_status,
+            visibility: post.visibility,
+            replies: vec![],
+            likes: vec![],
+            reposts: vec![],
+            mentions: vec![],
+        }
+    }
+
+    pub fn with_event(self, event: Event) -> Self {
+        Post {
+            event: Some(event),
+            ..self
+        }
+    }
+
+    pub fn with_review(self, review: Review) -> Self {
+        Post {
+            review: Some(review),
+            ..self
+        }
+    }
+
+    pub fn with_objects(self, objects: Vec<NestedObject>) -> Self {
+        Post { objects, ..self }
+    }
+
+    /// Adds a webmention to the replies, likes, reposts or mentions depending on its type.
+    pub fn with_mention(mut self, mention: DBMention) -> Self {
+        let list = match mention.mention_type.as_str() {
+            "reply" => &mut self.replies,
+            "like" => &mut self.likes,
+            "repost" => &mut self.reposts,
+            _ => &mut self.mentions,
+        };
+        list.push(mention.into());
+        self
+    }
+
+    /// Sets the checkin or location, depending on the location's property.
+    pub fn with_location(self, location: DBLocation) -> Self {
+        match location.property.as_str() {
+            "checkin" => Post { checkin: Some(location.into()), ..self },
+            _ => Post { location: Some(location.into()), ..self },
         }
     }
 }
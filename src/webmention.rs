@@ -0,0 +1,170 @@
+//! Verification of received webmentions (https://www.w3.org/TR/webmention/).
+//!
+//! The source page must link to the target. Its microformats (the first h-entry and its author
+//! h-card) decide whether the mention is a reply, like, repost or plain mention and who sent it.
+
+use scraper::{ElementRef, Html, Selector};
+use url::Url;
+
+/// Content longer than this many characters is cut short.
+const MAX_CONTENT_CHARS: usize = 500;
+
+/// What a verified source page says about its mention of the target.
+#[derive(Debug, Default, PartialEq)]
+pub struct SourceMention {
+    /// reply, like, repost or mention
+    pub mention_type: &'static str,
+    pub author_name: Option<String>,
+    pub author_url: Option<String>,
+    pub author_photo: Option<String>,
+    pub content: Option<String>,
+}
+
+/// Parses the HTML of a source page fetched from source_url. Returns None if it doesn't link to
+/// target.
+pub fn parse_source(html: &str, source_url: &Url, target: &Url) -> Option<SourceMention> {
+    let document = Html::parse_document(html);
+    let links_to_target = |root: ElementRef, selector: &str| {
+        let selector = Selector::parse(selector).expect("valid selector");
+        root.select(&selector).any(|el| {
+            el.value()
+                .attr("href")
+                .and_then(|href| source_url.join(href).ok())
+                .is_some_and(|url| &url == target)
+        })
+    };
+
+    if !links_to_target(document.root_element(), "[href]") {
+        return None;
+    }
+
+    let Some(entry) = select_first(document.root_element(), ".h-entry") else {
+        return Some(SourceMention {
+            mention_type: "mention",
+            ..Default::default()
+        });
+    };
+
+    let mention_type = if links_to_target(entry, ".u-in-reply-to[href], .u-in-reply-to .u-url[href]") {
+        "reply"
+    } else if links_to_target(entry, ".u-like-of[href], .u-like-of .u-url[href]") {
+        "like"
+    } else if links_to_target(entry, ".u-repost-of[href], .u-repost-of .u-url[href]") {
+        "repost"
+    } else {
+        "mention"
+    };
+
+    let mut mention = SourceMention {
+        mention_type,
+        content: select_first(entry, ".e-content")
+            .or_else(|| select_first(entry, ".p-content"))
+            .map(element_text)
+            .filter(|c| !c.is_empty())
+            .map(truncate_content),
+        ..Default::default()
+    };
+
+    if let Some(author) = select_first(entry, ".p-author, .u-author") {
+        let absolute = |url: &str| source_url.join(url).ok().map(String::from);
+        if author.value().classes().any(|c| c == "h-card") {
+            mention.author_name = select_first(author, ".p-name").map(element_text);
+            mention.author_url = select_first(author, ".u-url[href]")
+                .and_then(|el| el.value().attr("href"))
+                .or(author.value().attr("href"))
+                .and_then(absolute);
+            mention.author_photo = select_first(author, ".u-photo[src]")
+                .and_then(|el| el.value().attr("src"))
+                .and_then(absolute);
+        } else {
+            mention.author_url = author.value().attr("href").and_then(absolute);
+        }
+        if mention.author_name.is_none() {
+            mention.author_name = Some(element_text(author)).filter(|n| !n.is_empty());
+        }
+    }
+
+    Some(mention)
+}
+
+fn select_first<'a>(root: ElementRef<'a>, selector: &str) -> Option<ElementRef<'a>> {
+    let selector = Selector::parse(selector).expect("valid selector");
+    root.select(&selector).next()
+}
+
+/// The text of an element with whitespace collapsed.
+fn element_text(el: ElementRef) -> String {
+    el.text()
+        .flat_map(str::split_whitespace)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn truncate_content(content: String) -> String {
+    match content.char_indices().nth(MAX_CONTENT_CHARS) {
+        Some((i, _)) => format!("{}…", &content[..i]),
+        None => content,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_source, SourceMention};
+    use url::Url;
+
+    fn urls() -> (Url, Url) {
+        (
+            Url::parse("https://other.example.com/notes/1").unwrap(),
+            Url::parse("https://example.com/2024/01/01/hello").unwrap(),
+        )
+    }
+
+    #[test]
+    fn parse_reply_with_author() {
+        let (source, target) = urls();
+        let html = r#"
+            <div class="h-entry">
+              <a class="p-author h-card" href="/">
+                <img class="u-photo" src="/me.jpg" alt=""> <span class="p-name">Jane  Doe</span>
+              </a>
+              <a class="u-in-reply-to" href="https://example.com/2024/01/01/hello">in reply to</a>
+              <div class="e-content">Great <b>post</b>!</div>
+            </div>
+        "#;
+        assert_eq!(
+            parse_source(html, &source, &target),
+            Some(SourceMention {
+                mention_type: "reply",
+                author_name: Some("Jane Doe".into()),
+                author_url: Some("https://other.example.com/".into()),
+                author_photo: Some("https://other.example.com/me.jpg".into()),
+                content: Some("Great post !".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_like_and_repost() {
+        let (source, target) = urls();
+        let like = r#"<div class="h-entry"><a class="p-author" href="https://jane.example.com/">Jane</a>
+            <a class="u-like-of" href="https://example.com/2024/01/01/hello"></a></div>"#;
+        let parsed = parse_source(like, &source, &target).unwrap();
+        assert_eq!(parsed.mention_type, "like");
+        assert_eq!(parsed.author_name.as_deref(), Some("Jane"));
+        assert_eq!(parsed.author_url.as_deref(), Some("https://jane.example.com/"));
+
+        let repost = r#"<div class="h-entry"><div class="u-repost-of h-cite">
+            <a class="u-url" href="https://example.com/2024/01/01/hello">hello</a></div></div>"#;
+        assert_eq!(parse_source(repost, &source, &target).unwrap().mention_type, "repost");
+    }
+
+    #[test]
+    fn parse_plain_mention_and_missing_link() {
+        let (source, target) = urls();
+        let mention = r#"<p>See <a href="https://example.com/2024/01/01/hello">this</a></p>"#;
+        assert_eq!(parse_source(mention, &source, &target).unwrap().mention_type, "mention");
+
+        let unrelated = r#"<div class="h-entry"><a class="u-in-reply-to" href="https://example.com/2024/01/02/other">x</a></div>"#;
+        assert_eq!(parse_source(unrelated, &source, &target), None);
+    }
+}